@@ -1,33 +1,83 @@
-import { Fragment, useRef } from 'react';
+import { Fragment, useEffect, useMemo, useRef, useState } from 'react';
 import { LogsIcon } from 'lucide-react';
+import { Replicas } from './components/replicas';
 import { ThemeSelector } from './components/theme-selector';
 import { Xterm } from './components/xterm';
 import { WindowButtons } from './components/window-buttons';
+import { flushRemoteEvents } from './remote';
+import type { EventPayload } from './types';
+import { replicaTerminals } from './workspace';
 
 export default function App() {
   // DOM reference for buttons rendering
   const portalEl = useRef(null);
 
+  // Services run as numbered replicas, each shown as a tab of sub-tabs
+  const replicated = window.config.services.filter((service) => service.replicas != null);
+
+  // How many replicas of each replicated service run, by service id
+  const [replicas, setReplicas] = useState<Record<string, number>>(() =>
+    Object.fromEntries(replicated.map((service) => [service.id, service.replicas ?? 0])),
+  );
+
+  // Follow scaling, from this window or from elsewhere
+  // biome-ignore lint/correctness/useExhaustiveDependencies(replicated): The config doesn't change
+  useEffect(() => {
+    const unsubscribes = replicated.map((service) => {
+      const handler = (event: EventPayload) => {
+        if (event.type !== 'replicas') return;
+        const count = event.data as number;
+        replicaTerminals(service, count);
+        setReplicas((current) => ({ ...current, [service.id]: count }));
+      };
+      window.backend.on(service.id, handler);
+      return () => window.backend.off(service.id, handler);
+    });
+    return () => {
+      for (const unsubscribe of unsubscribes) unsubscribe();
+    };
+  }, []);
+
+  // Runs after every terminal's effects, so they're all listening by now
+  useEffect(() => {
+    flushRemoteEvents();
+  }, []);
+
+  // Replicas added by scaling join the "All" tab, and stay when scaled away
+  // biome-ignore lint/correctness/useExhaustiveDependencies(replicas): Terminals are added along with replicas
+  const allTerminals = useMemo(() => [...window.terminals], [replicas]);
+
   return (
     <div className="h-screen w-screen overflow-hidden">
       {/* Tab layout container */}
       <div className="tabs tabs-lift flex rounded-none pt-1 bg-base-300 relative w-full h-[calc(100%-10vh)] select-none">
         {/* Placeholder div for alignment/styling */}
 				<div className="title-bar min-w-50 grow flex justify-left items-center">
-		      <WindowButtons />
+		      {!window.remote && <WindowButtons />}
 				</div>
 
-        {/* One tab + terminal per command */}
-        {Object.values(window.terminals).map((terminal) => (
-          <Fragment key={terminal.service.id}>
+        {/* One tab + terminal per command, or per replicated service */}
+        {window.config.services.map((service) => (
+          <Fragment key={service.id}>
             <input
               type="radio"
               name="tab"
               className="tab border-none"
-              aria-label={terminal.service.name}
+              aria-label={
+                service.replicas != null
+                  ? `${service.name} (${replicas[service.id]})`
+                  : service.name
+              }
             />
             <div className="tab-content size-full border-none bg-base-100 border-base-300 p-6 rounded-none">
-              <Xterm terminals={[terminal]} portalEl={portalEl} />
+              {service.replicas != null ? (
+                <Replicas service={service} count={replicas[service.id]} portalEl={portalEl} />
+              ) : (
+                <Xterm
+                  terminals={window.terminals.filter((terminal) => terminal.service.id === service.id)}
+                  portalEl={portalEl}
+                />
+              )}
             </div>
           </Fragment>
         ))}
@@ -40,7 +90,7 @@ export default function App() {
         </label>
         <div className="tab-content size-full border-none bg-base-100 border-base-300 p-6 rounded-none">
           <Xterm
-            terminals={Object.values(window.terminals)} // All terminals passed in
+            terminals={allTerminals} // All terminals passed in
             portalEl={portalEl}
             aggregated // Special behavior for global log view
           />
@@ -0,0 +1,87 @@
+import { MinusIcon, PlusIcon } from "lucide-react";
+import { Fragment, useEffect, useState } from "react";
+import type { EventPayload, ServiceConfig } from "../types";
+import { replicaTerminals, scale } from "../workspace";
+import { Xterm } from "./xterm";
+
+export type Props = {
+	service: ServiceConfig; // The replicated service, as configured
+	count: number; // How many replicas run now
+	portalEl?: React.RefObject<HTMLDivElement | null>; // Element to mount control buttons into
+};
+
+// One sub-tab per replica of a service, with buttons to scale it
+export function Replicas(props: Props) {
+	const [selected, setSelected] = useState(0);
+	const [error, setError] = useState<string | null>(null);
+
+	// Remote viewers can watch but not act on the services
+	const readOnly = window.remote?.role === "viewer";
+
+	const terminals = replicaTerminals(props.service, props.count);
+
+	// The selected replica may have been scaled away
+	const active = Math.min(selected, props.count - 1);
+
+	// Scaling failures are reported on the service's own id
+	useEffect(() => {
+		const handler = (event: EventPayload) => {
+			if (event.type === "error") setError(event.data as string);
+			if (event.type === "replicas") setError(null);
+		};
+		window.backend.on(props.service.id, handler);
+		return () => window.backend.off(props.service.id, handler);
+	}, [props.service.id]);
+
+	return (
+		<div className="tabs tabs-border size-full">
+			{terminals.map((terminal, index) => (
+				<Fragment key={terminal.service.id}>
+					<input
+						type="radio"
+						name={`replicas-${props.service.id}`}
+						className="tab"
+						aria-label={`#${index + 1}`}
+						checked={index === active}
+						onChange={() => setSelected(index)}
+					/>
+					<div className="tab-content h-[calc(100%-2.5rem)] pt-2">
+						<Xterm terminals={[terminal]} portalEl={props.portalEl} />
+					</div>
+				</Fragment>
+			))}
+
+			{props.count === 0 && (
+				<div className="order-1 w-full p-4 text-sm opacity-70">
+					No replica of {props.service.name} is running
+				</div>
+			)}
+
+			{/* Scale buttons, next to the replica tabs */}
+			<div className="ml-auto flex items-center gap-1">
+				{error && <span className="text-error text-xs px-2">{error}</span>}
+				{!readOnly && (
+					<>
+						<button
+							type="button"
+							className="btn btn-circle btn-sm btn-ghost"
+							title="Remove a replica"
+							disabled={props.count === 0}
+							onClick={() => scale(props.service, props.count - 1)}
+						>
+							<MinusIcon size={16} />
+						</button>
+						<button
+							type="button"
+							className="btn btn-circle btn-sm btn-ghost"
+							title="Add a replica"
+							onClick={() => scale(props.service, props.count + 1)}
+						>
+							<PlusIcon size={16} />
+						</button>
+					</>
+				)}
+			</div>
+		</div>
+	);
+}
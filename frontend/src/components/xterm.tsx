@@ -1,14 +1,64 @@
 import { FitAddon } from "@xterm/addon-fit";
 import { WebLinksAddon } from "@xterm/addon-web-links";
 import { Terminal as XTermTerminal } from "@xterm/xterm";
-import { PaintBucketIcon, PlayIcon, SquareIcon } from "lucide-react";
+import {
+	ExternalLinkIcon,
+	LayersIcon,
+	PaintBucketIcon,
+	PauseIcon,
+	PlayIcon,
+	RotateCwIcon,
+	SquareIcon,
+	ZapIcon,
+} from "lucide-react";
 import { memo, useEffect, useRef, useState } from "react";
 import { useIntersection, useWindowSize, useLocalStorage } from "react-use";
 import { ansi } from "../utils/ansi";
+import { formatBytes, formatDuration } from "../utils/format";
 import { Portal } from "./portal";
 import { useXtermTheme } from "../hooks/use-xterm-theme";
 import type { Terminal } from "../terminal";
+import type {
+	PortConflict,
+	PortConflictChoice,
+	ServiceEndpoints,
+	ServiceStats,
+} from "../types";
 import { getAccentColors, getTerminalTheme } from "../utils/color";
+import {
+	groups,
+	restartAll,
+	startAll,
+	startGroup,
+	stopAll,
+} from "../workspace";
+
+// Signals offered for running services, with what they usually mean
+const SIGNALS: [string, string][] = [
+	["HUP", "Reload"],
+	["INT", "Interrupt"],
+	["TERM", "Terminate"],
+	["USR1", "User 1"],
+	["USR2", "User 2"],
+	["KILL", "Kill"],
+];
+
+// Printed URLs, plus a localhost URL for listening ports none of them mention
+function endpointLinks(endpoints: ServiceEndpoints): string[] {
+	const ports = new Set(
+		endpoints.urls.map((url) => {
+			try {
+				return Number(new URL(url).port);
+			} catch {
+				return 0;
+			}
+		}),
+	);
+	const extra = endpoints.ports
+		.filter((port) => !ports.has(port))
+		.map((port) => `http://localhost:${port}`);
+	return [...endpoints.urls, ...extra];
+}
 
 export type Props = {
 	terminals: Terminal[]; // One or more terminal instances
@@ -19,6 +69,13 @@ export type Props = {
 function Component(props: Props) {
 	const [tabIsActive, setTabIsActive] = useState(false);
 	const [isRunning, setIsRunning] = useState(false);
+	const [isPaused, setIsPaused] = useState(false);
+	const [stats, setStats] = useState<ServiceStats | null>(null);
+	const [links, setLinks] = useState<string[]>([]);
+	const [conflict, setConflict] = useState<PortConflict | null>(null);
+
+	// Remote viewers can watch but not act on the services
+	const readOnly = window.remote?.role === "viewer";
 
 	// DOM + Xterm instance refs
 	const terminalRef = useRef<HTMLDivElement>(document.createElement("div"));
@@ -50,7 +107,11 @@ function Component(props: Props) {
 	// Accent colors used to label output per terminal
 	const accentColors = useRef<Record<string, [string, string]>>({});
 
-	// Initialize terminal and connect it to backend logic
+	// Terminals that were already started once, so binding them again doesn't
+	// start them twice
+	const autostarted = useRef(new Set<string>());
+
+	// Initialize the terminal once, whichever services it shows
 	useEffect(() => {
 		const fitAddon = new FitAddon();
 		const webLinksAddon = new WebLinksAddon((_event, uri) =>
@@ -59,7 +120,7 @@ function Component(props: Props) {
 
 		const xterm = new XTermTerminal({
 			cursorBlink: !props.aggregated,
-			disableStdin: props.aggregated,
+			disableStdin: props.aggregated || readOnly,
 			cursorStyle: "underline",
 		});
 
@@ -68,20 +129,59 @@ function Component(props: Props) {
 		xterm.loadAddon(fitAddon);
 		xterm.loadAddon(webLinksAddon);
 
+		// Mount the terminal UI into the DOM
+		if (terminalRef.current) {
+			xterm.open(terminalRef.current);
+		}
+
+		// Prevent text input on aggregated terminals
+		if (props.aggregated && xterm.textarea) {
+			xterm.textarea.disabled = true;
+		}
+
+		xtermInstance.current = xterm;
+		fitAddonInstance.current = fitAddon;
+
+		return () => {
+			xterm.dispose();
+			xtermInstance.current = null;
+			fitAddonInstance.current = null;
+		};
+	}, [props.aggregated]);
+
+	// Replicas come and go, the "All" tab keeps its output when they do
+	const terminalIds = props.terminals.map((term) => term.service.id).join(",");
+
+	// Connect the terminal to the backend logic of its services
+	// biome-ignore lint/correctness/useExhaustiveDependencies(terminalIds): Should rebind only when the services change
+	useEffect(() => {
+		const xterm = xtermInstance.current;
+		if (!xterm) return;
+
 		const cleanupFns: (() => void)[] = [];
 
 		for (const term of props.terminals) {
 			// Only bind stdin for non-aggregated terminals
-			if (!props.aggregated) {
-				xterm.onData((data) => term.input(data));
+			if (!props.aggregated && !readOnly) {
+				const input = xterm.onData((data) => term.input(data));
+				cleanupFns.push(() => input.dispose());
 			}
 
-			xterm.onResize((size) => term.resize(size));
+			if (!readOnly) {
+				const resize = xterm.onResize((size) => term.resize(size));
+				cleanupFns.push(() => resize.dispose());
+			}
 
 			const outputId = crypto.randomUUID();
 			const stoppedId = crypto.randomUUID();
 			const runningId = crypto.randomUUID();
 			const errorId = crypto.randomUUID();
+			const exitedId = crypto.randomUUID();
+			const statsId = crypto.randomUUID();
+			const endpointsId = crypto.randomUUID();
+			const conflictId = crypto.randomUUID();
+			const pausedId = crypto.randomUUID();
+			const resumedId = crypto.randomUUID();
 
 			let noHistory = true;
 
@@ -117,6 +217,8 @@ function Component(props: Props) {
 					xterm.writeln(ansi.bgHex(accentBg).hex(accentFg).text(" ⏹ STOPPED "));
 				} else {
 					setIsRunning(false);
+					setIsPaused(false);
+					setStats(null);
 					xterm.options.disableStdin = false;
 					xterm.writeln(ansi.bgHex(accentBg).hex(accentFg).text(" ⏹ STOPPED "));
 				}
@@ -132,6 +234,8 @@ function Component(props: Props) {
 				} else {
 					xterm.options.disableStdin = true;
 					setIsRunning(true);
+					setIsPaused(false);
+					setConflict(null);
 				}
 			});
 
@@ -147,46 +251,87 @@ function Component(props: Props) {
 					);
 				} else {
 					setIsRunning(false);
+					setConflict(null);
 					xterm.writeln(
 						ansi.bgHex(accentBg).hex(accentFg).text(` ERROR: ${data} `),
 					);
 				}
 			});
 
+			term.onWithId(exitedId, "exited", ({ code, success }) => {
+				// Only tasks are expected to exit, so only they get a verdict
+				if (term.service.kind !== "task") return;
+
+				const [accentBg, accentFg] =
+					accentColors.current[term.service.id] ?? getAccentColors();
+				const verdict = success ? " ✔ DONE " : ` ✖ FAILED (exit code ${code}) `;
+				if (props.aggregated) {
+					xterm.write(
+						`${ansi.bgHex(accentBg).hex(accentFg).italic(` ${term.service.name} `)} `,
+					);
+				}
+				xterm.writeln(ansi.bgHex(accentBg).hex(accentFg).text(verdict));
+			});
+
+			for (const [id, event, label] of [
+				[pausedId, "paused", " ⏸ PAUSED "],
+				[resumedId, "resumed", " ▶ RESUMED "],
+			] as const) {
+				term.onWithId(id, event, () => {
+					const [accentBg, accentFg] =
+						accentColors.current[term.service.id] ?? getAccentColors();
+					if (props.aggregated) {
+						xterm.write(
+							`${ansi.bgHex(accentBg).hex(accentFg).italic(` ${term.service.name} `)} `,
+						);
+					} else {
+						setIsPaused(event === "paused");
+					}
+					xterm.writeln(ansi.bgHex(accentBg).hex(accentFg).text(label));
+				});
+			}
+
+			if (!props.aggregated) {
+				term.onWithId(statsId, "stats", setStats);
+				term.onWithId(endpointsId, "endpoints", (endpoints) =>
+					setLinks(endpointLinks(endpoints)),
+				);
+				term.onWithId(conflictId, "port_conflict", setConflict);
+			}
+
 			cleanupFns.push(() => {
 				term.offById(outputId);
 				term.offById(stoppedId);
 				term.offById(runningId);
 				term.offById(errorId);
+				term.offById(exitedId);
+				term.offById(statsId);
+				term.offById(endpointsId);
+				term.offById(conflictId);
+				term.offById(pausedId);
+				term.offById(resumedId);
 			});
 
-			// Mount the terminal UI into the DOM
-			if (terminalRef.current) {
-				xterm.open(terminalRef.current);
-				// Small delay before starting to avoid race conditions
+			// Small delay before starting to avoid race conditions. Remote
+			// browsers join a workspace that's already been started.
+			if (
+				term.service.autostart !== false &&
+				!window.remote &&
+				!autostarted.current.has(term.service.id)
+			) {
+				autostarted.current.add(term.service.id);
 				setTimeout(() => {
 					term.start();
 				}, 500);
 			}
 		}
 
-		// Prevent text input on aggregated terminals
-		if (props.aggregated && xterm.textarea) {
-			xterm.textarea.disabled = true;
-		}
-
-		xtermInstance.current = xterm;
-		fitAddonInstance.current = fitAddon;
-
 		return () => {
-			xterm.dispose();
-			xtermInstance.current = null;
-			fitAddonInstance.current = null;
 			for (const cleanupFn of cleanupFns) {
 				cleanupFn();
 			}
 		};
-	}, [props.terminals, props.aggregated]);
+	}, [terminalIds, props.aggregated]);
 
 	// Auto-fit terminal when visible
 	useEffect(() => {
@@ -220,7 +365,9 @@ function Component(props: Props) {
 		const colors: Record<string, [string, string]> = {};
 		for (const term of props.terminals) {
 			if (sortedColors) {
-				colors[term.service.id] = getAccentColors(Number(term.service.id));
+				colors[term.service.id] = getAccentColors(
+					Number.parseInt(term.service.id),
+				);
 			} else {
 				colors[term.service.id] = getAccentColors();
 			}
@@ -228,6 +375,11 @@ function Component(props: Props) {
 		accentColors.current = colors;
 	}, [theme, props.terminals, sortedColors]);
 
+	const resolveConflict = (choice: PortConflictChoice) => {
+		props.terminals[0].resolvePortConflict(choice);
+		setConflict(null);
+	};
+
 	return (
 		<>
 			{/* Terminal container */}
@@ -241,7 +393,7 @@ function Component(props: Props) {
 			{/* Controls mounted into portal (e.g., bottom bar) */}
 			<Portal container={props.portalEl?.current}>
 				{/* Start/Stop button (only for individual terminals) */}
-				{!props.aggregated && tabIsActive && (
+				{!props.aggregated && !readOnly && tabIsActive && (
 					<button
 						type="button"
 						className="btn btn-circle btn-sm btn-ghost"
@@ -254,6 +406,149 @@ function Component(props: Props) {
 					</button>
 				)}
 
+				{/* Restart button (only for running individual terminals) */}
+				{!props.aggregated && !readOnly && tabIsActive && isRunning && (
+					<button
+						type="button"
+						className="btn btn-circle btn-sm btn-ghost"
+						title="Restart"
+						onClick={() => props.terminals[0].restart()}
+					>
+						<RotateCwIcon size={16} />
+					</button>
+				)}
+
+				{/* Pause/Resume button (only for running individual terminals) */}
+				{!props.aggregated && !readOnly && tabIsActive && isRunning && (
+					<button
+						type="button"
+						className="btn btn-circle btn-sm btn-ghost"
+						title={isPaused ? "Resume" : "Pause"}
+						onClick={() => {
+							const method = isPaused ? "resume" : "pause";
+							props.terminals[0][method]();
+						}}
+					>
+						{isPaused ? <PlayIcon size={16} /> : <PauseIcon size={16} />}
+					</button>
+				)}
+
+				{/* Signal menu (only for running individual terminals) */}
+				{!props.aggregated && !readOnly && tabIsActive && isRunning && (
+					<div className="dropdown dropdown-top">
+						<button
+							type="button"
+							tabIndex={0}
+							className="btn btn-circle btn-sm btn-ghost"
+							title="Send signal"
+						>
+							<ZapIcon size={16} />
+						</button>
+						<ul className="dropdown-content menu bg-base-200 rounded-box z-10 w-48 p-2 shadow-sm">
+							{SIGNALS.map(([signal, meaning]) => (
+								<li key={signal}>
+									<button
+										type="button"
+										onClick={() => props.terminals[0].signal(signal)}
+									>
+										<span className="font-mono">SIG{signal}</span>
+										<span className="opacity-60">{meaning}</span>
+									</button>
+								</li>
+							))}
+						</ul>
+					</div>
+				)}
+
+				{/* Process stats (only for running individual terminals) */}
+				{!props.aggregated && tabIsActive && isRunning && stats && (
+					<div className="flex items-center gap-3 px-2 text-xs opacity-70 font-mono">
+						{stats.pid !== null && <span title="Process id">pid {stats.pid}</span>}
+						<span title="Uptime">up {formatDuration(stats.uptime)}</span>
+						{stats.restarts > 0 && (
+							<span title="Restarts">↻ {stats.restarts}</span>
+						)}
+						{stats.cpu !== null && (
+							<span title="CPU, whole process tree">{stats.cpu.toFixed(1)}%</span>
+						)}
+						{stats.memory !== null && (
+							<span title="Memory, whole process tree">
+								{formatBytes(stats.memory)}
+							</span>
+						)}
+					</div>
+				)}
+
+				{/* Where the service can be reached (only for individual terminals) */}
+				{!props.aggregated &&
+					tabIsActive &&
+					links.map((link) => (
+						<button
+							key={link}
+							type="button"
+							className="btn btn-sm btn-ghost font-mono text-xs"
+							title={`Open ${link}`}
+							onClick={() => props.terminals[0].openLink(link)}
+						>
+							<ExternalLinkIcon size={14} />
+							{link.replace(/^https?:\/\//, "")}
+						</button>
+					))}
+
+				{/* Start/Stop/Restart all buttons for the global tab */}
+				{props.aggregated && !readOnly && tabIsActive && (
+					<>
+						<button
+							type="button"
+							className="btn btn-circle btn-sm btn-ghost"
+							title="Start all"
+							onClick={startAll}
+						>
+							<PlayIcon size={16} />
+						</button>
+						<button
+							type="button"
+							className="btn btn-circle btn-sm btn-ghost"
+							title="Stop all"
+							onClick={stopAll}
+						>
+							<SquareIcon size={16} />
+						</button>
+						<button
+							type="button"
+							className="btn btn-circle btn-sm btn-ghost"
+							title="Restart all"
+							onClick={restartAll}
+						>
+							<RotateCwIcon size={16} />
+						</button>
+					</>
+				)}
+
+				{/* Group starter for the global tab */}
+				{props.aggregated && !readOnly && tabIsActive && groups().length > 0 && (
+					<div className="dropdown dropdown-top">
+						<button
+							type="button"
+							tabIndex={0}
+							className="btn btn-circle btn-sm btn-ghost"
+							title="Start group"
+						>
+							<LayersIcon size={16} />
+						</button>
+						<ul className="dropdown-content menu bg-base-200 rounded-box z-10 w-48 p-2 shadow-sm">
+							{groups().map((group) => (
+								<li key={group}>
+									<button type="button" onClick={() => startGroup(group)}>
+										<PlayIcon size={14} />
+										{group}
+									</button>
+								</li>
+							))}
+						</ul>
+					</div>
+				)}
+
 				{/* Color-sorting toggle for the global tab */}
 				{props.aggregated && tabIsActive && (
 					<button
@@ -276,6 +571,50 @@ function Component(props: Props) {
 					</button>
 				)}
 			</Portal>
+
+			{/* Port conflict prompt, shown whichever tab is active */}
+			<Portal container={document.body}>
+				{!props.aggregated && !readOnly && conflict && (
+					<div className="modal modal-open">
+						<div className="modal-box">
+							<h3 className="font-bold text-lg">
+								Port {conflict.port} is already in use
+							</h3>
+							<p className="py-4">
+								{props.terminals[0].service.name} can't start cleanly,{" "}
+								{conflict.pid !== null
+									? `${conflict.process ?? "a process"} (pid ${conflict.pid}) is listening on it.`
+									: "another process is listening on it."}
+							</p>
+							<div className="modal-action">
+								{conflict.pid !== null && (
+									<button
+										type="button"
+										className="btn btn-error"
+										onClick={() => resolveConflict("kill")}
+									>
+										Kill {conflict.process ?? "process"}
+									</button>
+								)}
+								<button
+									type="button"
+									className="btn"
+									onClick={() => resolveConflict("start")}
+								>
+									Start anyway
+								</button>
+								<button
+									type="button"
+									className="btn btn-ghost"
+									onClick={() => resolveConflict("abort")}
+								>
+									Abort
+								</button>
+							</div>
+						</div>
+					</div>
+				)}
+			</Portal>
 		</>
 	);
 }
@@ -5,14 +5,37 @@ import mitt from 'mitt';
 import App from './app';
 import { ErrorBoundary } from './components/error-boundary';
 import { Terminal } from './terminal';
+import { connectRemote } from './remote';
+import { replicaTerminals } from './workspace';
 
 window.backend = mitt();
-window.terminals = window.config.services.map((service) => new Terminal(service));
 
-ReactDOM.createRoot(document.getElementById('root') as HTMLElement).render(
-  <React.StrictMode>
-    <ErrorBoundary>
-      <App />
-    </ErrorBoundary>
-  </React.StrictMode>,
-);
+function render() {
+  window.terminals = [];
+  for (const service of window.config.services) {
+    if (service.replicas != null) {
+      replicaTerminals(service, service.replicas);
+    } else {
+      window.terminals.push(new Terminal(service));
+    }
+  }
+
+  ReactDOM.createRoot(document.getElementById('root') as HTMLElement).render(
+    <React.StrictMode>
+      <ErrorBoundary>
+        <App />
+      </ErrorBoundary>
+    </React.StrictMode>,
+  );
+}
+
+// Without the webview's IPC, this is a browser connecting through `runz --serve`
+if (window.ipc) {
+  render();
+} else {
+  connectRemote()
+    .then(render)
+    .catch((error: Error) => {
+      document.body.textContent = error.message;
+    });
+}
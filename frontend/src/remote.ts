@@ -0,0 +1,94 @@
+import type { Config, EventPayload } from './types';
+
+type Hello = {
+  type: 'hello';
+  role: 'operator' | 'viewer';
+  config: Config;
+};
+
+type RemoteEvent = {
+  id: string;
+  payload: EventPayload;
+};
+
+// Events received before the terminals listen for them
+let pending: RemoteEvent[] | null = [];
+
+// Sends commands over a WebSocket of their own, queueing them until it's open
+function commandSocket(url: string): (body: string) => void {
+  const socket = new WebSocket(url);
+  let queued: string[] | null = [];
+
+  socket.onopen = () => {
+    for (const body of queued ?? []) socket.send(body);
+    queued = null;
+  };
+
+  // Commands that were refused come back as events
+  socket.onmessage = (message) => {
+    const event = JSON.parse(message.data) as RemoteEvent;
+    window.backend.emit(event.id, event.payload);
+  };
+
+  return (body) => {
+    if (queued) {
+      queued.push(body);
+    } else {
+      socket.send(body);
+    }
+  };
+}
+
+// Connects to the workspace over WebSocket when the UI is opened in a
+// browser through `runz --serve`, standing in for the webview's IPC. Events
+// and commands each get a socket.
+export function connectRemote(): Promise<void> {
+  return new Promise((resolve, reject) => {
+    const protocol = location.protocol === 'https:' ? 'wss:' : 'ws:';
+    const socket = new WebSocket(`${protocol}//${location.host}/ws`);
+    let connected = false;
+
+    socket.onmessage = (message) => {
+      const data = JSON.parse(message.data);
+
+      // The first message tells who we are and what the workspace looks like
+      if (!connected) {
+        const hello = data as Hello;
+        connected = true;
+        window.config = hello.config;
+        window.remote = { role: hello.role };
+        const commands = `${protocol}//${location.host}/ws/commands`;
+        window.ipc = { postMessage: commandSocket(commands) };
+        document.title = hello.config.name ?? 'runz';
+        resolve();
+        return;
+      }
+
+      const event = data as RemoteEvent;
+      if (pending) {
+        pending.push(event);
+      } else {
+        window.backend.emit(event.id, event.payload);
+      }
+    };
+
+    socket.onclose = () => {
+      if (!connected) {
+        reject(new Error('Could not connect to the workspace'));
+        return;
+      }
+      document.title = `${window.config.name} (disconnected)`;
+    };
+  });
+}
+
+// Delivers what arrived while the UI was mounting, then everything live
+export function flushRemoteEvents() {
+  if (!pending) return;
+
+  const events = pending;
+  pending = null;
+  for (const event of events) {
+    window.backend.emit(event.id, event.payload);
+  }
+}
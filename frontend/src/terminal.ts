@@ -1,5 +1,5 @@
 import mitt, { type Emitter } from 'mitt';
-import type { EventPayload, EventTypes, ServiceConfig } from './types';
+import type { EventPayload, EventTypes, PortConflictChoice, ServiceConfig } from './types';
 
 type Handler<K extends keyof EventTypes> = (data: EventTypes[K]) => void;
 
@@ -84,6 +84,53 @@ export class Terminal {
     }));
   }
 
+  restart() {
+    window.ipc.postMessage(JSON.stringify({
+      id: this.service.id,
+      event: {
+        name: 'restart_terminal',
+      }
+    }));
+  }
+
+  pause() {
+    window.ipc.postMessage(JSON.stringify({
+      id: this.service.id,
+      event: {
+        name: 'pause_terminal',
+      }
+    }));
+  }
+
+  resume() {
+    window.ipc.postMessage(JSON.stringify({
+      id: this.service.id,
+      event: {
+        name: 'resume_terminal',
+      }
+    }));
+  }
+
+  signal(name: string) {
+    window.ipc.postMessage(JSON.stringify({
+      id: this.service.id,
+      event: {
+        name: 'signal_terminal',
+        payload: name,
+      }
+    }));
+  }
+
+  resolvePortConflict(choice: PortConflictChoice) {
+    window.ipc.postMessage(JSON.stringify({
+      id: this.service.id,
+      event: {
+        name: 'resolve_port_conflict',
+        payload: choice,
+      }
+    }));
+  }
+
   input(data: string) {
     window.ipc.postMessage(JSON.stringify({
       id: this.service.id,
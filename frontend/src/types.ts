@@ -7,11 +7,21 @@ export type ServiceConfig = {
   args?: string[];
   wdir?: string;
   name?: string;
+  kind?: "service" | "task";
+  depends_on?: string[];
+  autostart?: boolean;
+  groups?: string[];
+  ports?: number[];
+  // Set for services run as numbered replicas, to how many run now
+  replicas?: number | null;
+  port_offset?: number;
 };
 
 export type Config = {
   name?: string;
   services: ServiceConfig[];
+  profiles?: string[];
+  detach?: boolean;
 };
 
 export type EventTypes = {
@@ -19,6 +29,41 @@ export type EventTypes = {
   stopped: undefined;
   output: string;
   error: string;
+  exited: { code: number; success: boolean };
+  stats: ServiceStats;
+  endpoints: ServiceEndpoints;
+  port_conflict: PortConflict;
+  paused: undefined;
+  resumed: undefined;
+  // Sent on a replicated service's own id when it's scaled
+  replicas: number;
+};
+
+// A declared port was taken when the service was about to start. The owner
+// is only known on Linux, and only for processes of the same user.
+export type PortConflict = {
+  port: number;
+  pid: number | null;
+  process: string | null;
+};
+
+export type PortConflictChoice = "kill" | "start" | "abort";
+
+// URLs found in a service's output and ports its process tree listens on
+// (the latter on Linux only)
+export type ServiceEndpoints = {
+  urls: string[];
+  ports: number[];
+};
+
+// Sampled every couple of seconds while a service runs. CPU and memory
+// cover the whole process tree and are only available on Linux.
+export type ServiceStats = {
+  pid: number | null;
+  uptime: number;
+  restarts: number;
+  cpu: number | null;
+  memory: number | null;
 };
 
 export type EventPayload = {
@@ -31,6 +76,10 @@ declare global {
     backend: Emitter<Record<string, EventPayload>>;
     config: Config;
     terminals: Terminal[];
+    // Set when the UI is opened in a browser through `runz --serve`
+    remote?: {
+      role: "operator" | "viewer";
+    };
     ipc: {
       postMessage: (message: string) => void;
     };
@@ -0,0 +1,65 @@
+import { Terminal } from './terminal';
+import type { ServiceConfig } from './types';
+
+// Workspace-wide operations, not tied to a single terminal
+function post(name: string, payload?: unknown, id = '') {
+  window.ipc.postMessage(JSON.stringify({
+    id,
+    event: {
+      name,
+      payload,
+    }
+  }));
+}
+
+// Every group used by a service, in order of first appearance
+export function groups(): string[] {
+  const names = window.config.services.flatMap((service) => service.groups ?? []);
+  return [...new Set(names)];
+}
+
+export function startGroup(group: string) {
+  post('start_group_terminals', group);
+}
+
+export function startAll() {
+  post('start_all_terminals');
+}
+
+export function stopAll() {
+  post('stop_all_terminals');
+}
+
+export function restartAll() {
+  post('restart_all_terminals');
+}
+
+// Terminals of replicas, kept once created so scaling back up reuses them
+const replicas = new Map<string, Terminal>();
+
+// The terminal of a service's replica, numbered from 1 like its id `0#1`
+export function replicaTerminal(service: ServiceConfig, number: number): Terminal {
+  const id = `${service.id}#${number}`;
+  let terminal = replicas.get(id);
+  if (!terminal) {
+    terminal = new Terminal({
+      ...service,
+      id,
+      name: `${service.name ?? service.prog}#${number}`,
+      replicas: null,
+      // The backend starts the ones added by scaling
+      autostart: service.autostart !== false && number <= (service.replicas ?? 0),
+    });
+    replicas.set(id, terminal);
+    window.terminals.push(terminal);
+  }
+  return terminal;
+}
+
+export function replicaTerminals(service: ServiceConfig, count: number): Terminal[] {
+  return Array.from({ length: count }, (_, index) => replicaTerminal(service, index + 1));
+}
+
+export function scale(service: ServiceConfig, count: number) {
+  post('scale_terminal', count, service.id);
+}
@@ -0,0 +1,134 @@
+use flate2::{write::GzEncoder, Compression};
+use parking_lot::Mutex;
+use rust_embed::RustEmbed;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+use wry::http;
+
+/// Where the webview loads the UI from, through the `runz` custom protocol.
+/// Windows and Android map custom protocols onto http.
+#[cfg(any(windows, target_os = "android"))]
+pub const PROTOCOL_URL: &str = "http://runz.localhost/";
+#[cfg(not(any(windows, target_os = "android")))]
+pub const PROTOCOL_URL: &str = "runz://localhost/";
+
+/// The built frontend, embedded at compile time.
+#[derive(RustEmbed)]
+#[folder = "frontend/dist/"]
+struct Dist;
+
+/// A file of the frontend bundle.
+pub struct Asset {
+    pub data: Cow<'static, [u8]>,
+    pub mime: String,
+    pub etag: String,
+    // Vite puts a content hash in the name of everything under assets/
+    pub immutable: bool,
+}
+
+impl Asset {
+    pub fn compressible(&self) -> bool {
+        self.mime.starts_with("text/")
+            || self.mime.ends_with("javascript")
+            || self.mime.ends_with("json")
+            || self.mime.ends_with("+xml")
+            || self.mime == "application/wasm"
+    }
+
+    pub fn cache_control(&self) -> &'static str {
+        if self.immutable {
+            "public, max-age=31536000, immutable"
+        } else {
+            "no-cache"
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    // Gzipped assets by ETag, so each one is compressed once
+    static ref GZIPPED: Mutex<HashMap<String, Arc<Vec<u8>>>> = Mutex::new(HashMap::new());
+}
+
+/// Looks up the file a request path points at, `/` being index.html.
+pub fn get(path: &str) -> Option<Asset> {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let path = match path.trim_start_matches('/') {
+        "" => "index.html",
+        path => path,
+    };
+
+    let file = Dist::get(path)?;
+    let hash: String = file
+        .metadata
+        .sha256_hash()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+
+    Some(Asset {
+        mime: file.metadata.mimetype().to_string(),
+        etag: format!("\"{}\"", &hash[..32]),
+        immutable: path.starts_with("assets/"),
+        data: file.data,
+    })
+}
+
+/// The asset compressed with gzip.
+pub fn gzipped(asset: &Asset) -> Arc<Vec<u8>> {
+    let mut cache = GZIPPED.lock();
+    if let Some(data) = cache.get(&asset.etag) {
+        return data.clone();
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    // Writing into a Vec can't fail
+    encoder.write_all(&asset.data).unwrap();
+    let data = Arc::new(encoder.finish().unwrap());
+    cache.insert(asset.etag.clone(), data.clone());
+    data
+}
+
+/// Answers a request for a file of the bundle, honoring If-None-Match.
+/// Compression is left out when nothing goes over the network.
+pub fn response(
+    request: &http::Request<Vec<u8>>,
+    compress: bool,
+) -> http::Response<Cow<'static, [u8]>> {
+    let Some(asset) = get(request.uri().path()) else {
+        return http::Response::builder()
+            .status(404)
+            .body(Cow::Borrowed(&b"Not Found"[..]))
+            .unwrap();
+    };
+
+    let header = |name: http::header::HeaderName| {
+        request
+            .headers()
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|item| item.split(';').next().unwrap_or_default().trim())
+            .collect::<Vec<_>>()
+    };
+
+    let builder = http::Response::builder()
+        .header(http::header::ETAG, &asset.etag)
+        .header(http::header::CACHE_CONTROL, asset.cache_control());
+    if header(http::header::IF_NONE_MATCH).contains(&asset.etag.as_str()) {
+        return builder.status(304).body(Cow::Borrowed(&[][..])).unwrap();
+    }
+
+    let builder = builder
+        .header(http::header::CONTENT_TYPE, &asset.mime)
+        .header(http::header::VARY, "Accept-Encoding");
+    if compress && asset.compressible() && header(http::header::ACCEPT_ENCODING).contains(&"gzip") {
+        return builder
+            .header(http::header::CONTENT_ENCODING, "gzip")
+            .body(Cow::Owned(gzipped(&asset).to_vec()))
+            .unwrap();
+    }
+    builder.body(asset.data).unwrap()
+}
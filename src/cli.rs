@@ -0,0 +1,200 @@
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use once_cell::sync::OnceCell;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use crate::config::{self, ServiceKind};
+use crate::import::Source;
+
+static CLI: OnceCell<Cli> = OnceCell::new();
+
+/// Runs the services of a workspace, each in its own terminal.
+#[derive(Debug, Parser)]
+#[command(name = "runz", version, about)]
+pub struct Cli {
+    /// Config file to use, instead of looking for runz.yml
+    #[arg(short, long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Inline config, as <format>|<data>
+    #[arg(short, long, global = true, value_name = "FORMAT|DATA")]
+    pub raw_config: Option<String>,
+
+    /// Only autostart the services of these groups
+    #[arg(
+        short,
+        long,
+        global = true,
+        value_name = "GROUP",
+        value_delimiter = ',',
+        env = "RUNZ_PROFILE"
+    )]
+    pub profile: Vec<String>,
+
+    /// Stay attached to the terminal instead of running in the background
+    #[arg(long, global = true, visible_alias = "no-detach")]
+    pub foreground: bool,
+
+    /// Serve the UI over HTTP on this address, instead of in-process (release
+    /// builds only)
+    #[arg(long, global = true, value_name = "ADDR")]
+    pub listen: Option<SocketAddr>,
+
+    /// Let browsers watch and control the workspace from this address
+    #[arg(long, global = true, value_name = "ADDR")]
+    pub serve: Option<SocketAddr>,
+
+    /// Serve Prometheus metrics of every service at /metrics on this address
+    #[arg(long, global = true, value_name = "ADDR")]
+    pub metrics: Option<SocketAddr>,
+
+    // Marks the process relaunched in the background, see `detach`
+    #[arg(long, global = true, hide = true)]
+    pub child: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Open the workspace (the default)
+    Run,
+    /// Load the config, report what's wrong with it and exit
+    Check,
+    /// Control the services of a running workspace
+    Ctl {
+        #[command(subcommand)]
+        action: CtlAction,
+    },
+    /// Generate a runz.yml from a Procfile, package.json or compose file
+    Init {
+        /// What to import from
+        #[arg(long, value_enum, ignore_case = true)]
+        from: Source,
+        /// File to import, defaults to the usual name in the current directory
+        file: Option<PathBuf>,
+        /// Overwrite an existing runz.yml
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the JSON Schema of the config format
+    Schema,
+    /// Print the completion script for a shell
+    Completions { shell: Shell },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CtlAction {
+    /// List the services and their state
+    List,
+    /// Show the state, pid, readiness, last exit code and endpoints of services
+    Status {
+        /// Services to show, by name, defaults to all of them
+        services: Vec<String>,
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+        /// Wait until the services are ready: running and listening on their
+        /// ports, or for tasks, completed successfully
+        #[arg(long)]
+        wait_ready: bool,
+        /// How long to wait for, in seconds
+        #[arg(
+            long,
+            value_name = "SECS",
+            default_value_t = 60,
+            requires = "wait_ready"
+        )]
+        timeout: u64,
+    },
+    /// Start services, by name
+    Start {
+        #[arg(required = true)]
+        services: Vec<String>,
+    },
+    /// Stop services, by name
+    Stop {
+        #[arg(required = true)]
+        services: Vec<String>,
+    },
+    /// Restart services, by name
+    Restart {
+        #[arg(required = true)]
+        services: Vec<String>,
+    },
+    /// Freeze services, by name, keeping their state until resumed
+    Pause {
+        #[arg(required = true)]
+        services: Vec<String>,
+    },
+    /// Resume paused services, by name
+    Resume {
+        #[arg(required = true)]
+        services: Vec<String>,
+    },
+    /// Change how many replicas of a service run
+    Scale { service: String, replicas: u32 },
+    /// Send a signal to services, by name
+    Signal {
+        /// Signal name or number, e.g. HUP, SIGUSR1 or 10
+        signal: String,
+        #[arg(required = true)]
+        services: Vec<String>,
+    },
+    /// Start every service, in dependency order
+    StartAll,
+    /// Stop every service
+    StopAll,
+    /// Restart every service
+    RestartAll,
+    /// Start the services of a group
+    StartGroup { group: String },
+}
+
+/// The parsed command line. Exits with the usage on invalid arguments.
+pub fn args() -> &'static Cli {
+    CLI.get_or_init(Cli::parse)
+}
+
+pub fn print_completions(shell: Shell) {
+    clap_complete::generate(shell, &mut Cli::command(), "runz", &mut std::io::stdout());
+}
+
+/// Loads the config like `run` would and lists its services.
+pub fn check() -> anyhow::Result<()> {
+    let config = config::get_config()?;
+
+    println!("{}: {} services", config.name, config.services.len());
+    for service in config.dependency_order() {
+        let mut details = vec![];
+        if service.kind == ServiceKind::Task {
+            details.push("task".to_string());
+        }
+        if !service.depends_on.is_empty() {
+            let names: Vec<&str> = service
+                .depends_on
+                .iter()
+                .filter_map(|id| config.find_service(id))
+                .map(|s| s.name.as_str())
+                .collect();
+            details.push(format!("after {}", names.join(", ")));
+        }
+        if let Some(replicas) = service.replicas {
+            let plural = if replicas == 1 { "" } else { "s" };
+            details.push(format!("{} replica{}", replicas, plural));
+        }
+        if !service.autostart {
+            details.push("manual".to_string());
+        }
+
+        if details.is_empty() {
+            println!("  {}", service.name);
+        } else {
+            println!("  {} ({})", service.name, details.join("; "));
+        }
+    }
+
+    Ok(())
+}
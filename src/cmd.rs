@@ -1,4 +1,10 @@
-use crate::pty_manager::{resize_pty, spawn_pty, stop_pty, write_input};
+use crate::config::ServiceConfig;
+use crate::emitter;
+use crate::pty_manager::{
+    parse_signal, pause_pty, resize_pty, restart_pty, resume_pty, signal_pty, stop_pty, write_input,
+};
+use crate::replicas;
+use crate::scheduler::{self, start_service};
 
 pub fn open_link(uri: String) -> Result<(), String> {
     println!("Opening link: {}", uri);
@@ -17,15 +23,112 @@ pub fn open_link(uri: String) -> Result<(), String> {
     }
 }
 
+// Looks up a service or replica the frontend or `runz ctl` refers to
+fn find(id: &str) -> Result<ServiceConfig, String> {
+    let config = crate::config::get_config().map_err(|e| e.to_string())?;
+    replicas::find(config, id).ok_or_else(|| format!("Unknown service '{}'", id))
+}
+
 pub fn start_terminal(id: String) -> Result<(), String> {
-    let config = crate::config::get_config().unwrap();
-    let service = config.services.iter().find(|s| s.id == id).unwrap();
+    let service = find(&id)?;
 
-    spawn_pty(&service)
+    if service.depends_on.is_empty()
+        && service.hooks.pre_start.is_none()
+        && service.ports.is_empty()
+    {
+        return start_service(&service);
+    }
+
+    // Waiting on dependencies, hooks or port conflicts must not block the caller
+    std::thread::spawn(move || {
+        if let Err(e) = start_service(&service) {
+            emitter::emit(service.id, serde_json::json!({ "type": "error", "data": e }));
+        }
+    });
+
+    Ok(())
+}
+
+pub fn resolve_port_conflict(id: String, choice: String) -> Result<(), String> {
+    crate::ports::resolve(&id, &choice)
 }
 
 pub fn stop_terminal(id: String) -> Result<(), String> {
-    stop_pty(&id)
+    let service = find(&id)?;
+
+    if service.hooks.pre_stop.is_none() {
+        return stop_pty(&id);
+    }
+
+    // Waiting on the hook must not block the caller
+    std::thread::spawn(move || {
+        if let Err(e) = stop_pty(&id) {
+            emitter::emit(id, serde_json::json!({ "type": "error", "data": e }));
+        }
+    });
+
+    Ok(())
+}
+
+pub fn restart_terminal(id: String) -> Result<(), String> {
+    let service = find(&id)?;
+
+    // Waiting for the old process to go away must not block the caller
+    std::thread::spawn(move || {
+        if let Err(e) = restart_pty(&service) {
+            emitter::emit(service.id, serde_json::json!({ "type": "error", "data": e }));
+        }
+    });
+
+    Ok(())
+}
+
+/// Delivers a signal, given by name such as `HUP` or `SIGUSR1`, to the
+/// service's process group.
+pub fn signal_terminal(id: String, signal: String) -> Result<(), String> {
+    let number = parse_signal(&signal).ok_or_else(|| format!("Unknown signal '{}'", signal))?;
+
+    // Keep track of services frozen this way, like pausing them would
+    #[cfg(unix)]
+    match number {
+        libc::SIGSTOP => return pause_pty(&id),
+        libc::SIGCONT => return resume_pty(&id),
+        _ => {}
+    }
+    signal_pty(&id, number)
+}
+
+pub fn scale_terminal(id: String, replicas: u32) -> Result<(), String> {
+    replicas::scale(&id, replicas)
+}
+
+pub fn pause_terminal(id: String) -> Result<(), String> {
+    pause_pty(&id)
+}
+
+pub fn resume_terminal(id: String) -> Result<(), String> {
+    resume_pty(&id)
+}
+
+pub fn start_all_terminals() -> Result<(), String> {
+    scheduler::start_all();
+    Ok(())
+}
+
+pub fn start_group_terminals(group: String) -> Result<(), String> {
+    scheduler::start_group(&group);
+    Ok(())
+}
+
+pub fn stop_all_terminals() -> Result<(), String> {
+    // Stopping runs hooks one after the other, which must not block the caller
+    std::thread::spawn(scheduler::stop_all);
+    Ok(())
+}
+
+pub fn restart_all_terminals() -> Result<(), String> {
+    std::thread::spawn(scheduler::restart_all);
+    Ok(())
 }
 
 pub fn input_terminal(id: String, data: String) -> Result<(), String> {
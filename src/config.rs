@@ -1,8 +1,16 @@
 use anyhow::Ok;
 use once_cell::sync::OnceCell;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use path_clean::PathClean;
+use slugify::slugify;
+
+use crate::cli;
+use crate::interpolate::Interpolator;
+use crate::layers::{self, Layered};
+use crate::ports::Choice;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServiceConfig {
@@ -11,26 +19,229 @@ pub struct ServiceConfig {
     pub args: Vec<String>,
     pub name: String,
     pub wdir: String,
+    pub watch: Option<WatchConfig>,
+    pub kind: ServiceKind,
+    pub depends_on: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub hooks: HooksConfig,
+    pub autostart: bool,
+    pub groups: Vec<String>,
+    pub ports: Vec<u16>,
+    // How many instances run at first, set on replicated services only.
+    // Their replicas are derived with `replica`.
+    pub replicas: Option<u32>,
+    pub port_offset: u16,
+    // Applied to port conflicts instead of asking
+    pub on_port_conflict: Option<Choice>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A service as written in the config file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct RawServiceConfig {
+    /// Program to run
     pub prog: String,
+    /// Arguments passed to the program
     pub args: Option<Vec<String>>,
+    /// Display name, defaults to the program
     pub name: Option<String>,
+    /// Working directory, relative to the directory of the file setting it
     pub wdir: Option<String>,
+    /// Restart or signal the service when its files change
+    pub watch: Option<RawWatchConfig>,
+    /// Whether the service keeps running or is a task expected to exit
+    pub kind: Option<ServiceKind>,
+    /// Names of the services to start first
+    pub depends_on: Option<Vec<String>>,
+    /// Extra environment variables
+    pub env: Option<BTreeMap<String, String>>,
+    /// Shell command run before starting, aborting the start on failure
+    pub pre_start: Option<String>,
+    /// Shell command run once the service listens on its ports, or started
+    /// when it has none
+    pub post_start: Option<String>,
+    /// Shell command run before stopping
+    pub pre_stop: Option<String>,
+    /// Shell command run once the service exited
+    pub post_stop: Option<String>,
+    /// Start the service when runz opens, defaults to true
+    pub autostart: Option<bool>,
+    /// Groups the service belongs to, selectable with --profile
+    pub groups: Option<Vec<String>>,
+    /// TCP ports the service listens on, checked before it starts
+    pub ports: Option<Vec<u16>>,
+    /// Run this many instances, named `<name>#1` to `<name>#N`, and allow
+    /// scaling them at runtime
+    pub replicas: Option<u32>,
+    /// Added to `ports` once more for each replica after the first
+    pub port_offset: Option<u16>,
+    /// What to do when one of `ports` is taken, instead of asking
+    pub on_port_conflict: Option<Choice>,
+}
+
+/// Shell commands run around a service's lifecycle.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HooksConfig {
+    pub pre_start: Option<String>,
+    pub post_start: Option<String>,
+    pub pre_stop: Option<String>,
+    pub post_stop: Option<String>,
+}
+
+/// Services are expected to keep running, tasks are expected to exit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceKind {
+    #[default]
+    Service,
+    Task,
+}
+
+/// What to do with a service when one of its watched files changes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchAction {
+    Restart,
+    Signal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchConfig {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    pub debounce: u64,
+    pub action: WatchAction,
+    pub signal: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RawWatchConfig {
+    /// Globs of the files to watch, defaults to everything
+    pub include: Option<Vec<String>>,
+    /// Globs of the files to ignore, defaults to .git
+    pub exclude: Option<Vec<String>>,
+    /// Milliseconds to wait for changes to settle, defaults to 500
+    pub debounce: Option<u64>,
+    /// What to do on change, defaults to restart
+    pub action: Option<WatchAction>,
+    /// Signal sent with the `signal` action, e.g. HUP
+    pub signal: Option<String>,
+}
+
+impl WatchConfig {
+    fn from_raw(raw: RawWatchConfig, service_name: &str) -> anyhow::Result<Self> {
+        let action = raw.action.unwrap_or(WatchAction::Restart);
+
+        if action == WatchAction::Signal {
+            match raw.signal.as_deref() {
+                // Without signals there are no names to check, the watcher
+                // reports it can't send one instead of the config failing to load
+                Some(_) if cfg!(not(unix)) => {}
+                Some(signal) if crate::pty_manager::parse_signal(signal).is_some() => {}
+                Some(signal) => anyhow::bail!(
+                    "Service '{}' watches with an unknown signal '{}'",
+                    service_name,
+                    signal
+                ),
+                None => anyhow::bail!(
+                    "Service '{}' watches with action 'signal' but no signal defined",
+                    service_name
+                ),
+            }
+        }
+
+        for pattern in raw.include.iter().chain(raw.exclude.iter()).flatten() {
+            if let Err(err) = globset::Glob::new(pattern) {
+                anyhow::bail!("Service '{}' has an invalid watch glob: {}", service_name, err);
+            }
+        }
+
+        Ok(WatchConfig {
+            include: raw.include.unwrap_or(vec!["**/*".to_string()]),
+            exclude: raw.exclude.unwrap_or(vec!["**/.git/**".to_string()]),
+            debounce: raw.debounce.unwrap_or(500),
+            action,
+            signal: raw.signal,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub name: String,
     pub services: Vec<ServiceConfig>,
+    // Groups selected with --profile, empty when everything is active
+    pub profiles: Vec<String>,
+    // Whether runz relaunches itself in the background on Unix
+    pub detach: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A runz workspace config, in YAML, JSON or TOML.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(title = "runz config")]
 pub struct RawConfig {
+    /// Workspace name
     pub name: String,
     pub services: Vec<RawServiceConfig>,
+    /// Variables available to `${...}` references in service values
+    pub vars: Option<BTreeMap<String, String>>,
+    /// Config file(s) this one is based on
+    #[serde(default)]
+    #[schemars(schema_with = "path_list_schema")]
+    pub extends: Option<Vec<String>>,
+    /// Config file(s) whose services and vars are added to this one
+    #[serde(default)]
+    #[schemars(schema_with = "path_list_schema")]
+    pub include: Option<Vec<String>>,
+    /// Run in the background, returning the shell prompt right away,
+    /// defaults to true
+    pub detach: Option<bool>,
+    // Directory services without a `wdir` run in, set while loading to the
+    // config file's directory
+    #[serde(skip)]
+    pub root: Option<PathBuf>,
+}
+
+// `extends` and `include` take a single path or a list of them
+fn path_list_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+    schemars::json_schema!({
+        "anyOf": [
+            { "type": "string" },
+            { "type": "array", "items": { "type": "string" } }
+        ]
+    })
+}
+
+// Any file can be the base or the override of another, so nothing is
+// required until the files are merged, and lists take `key+` to append
+fn allow_layering(schema: &mut serde_json::Value) {
+    match schema {
+        serde_json::Value::Object(map) => {
+            map.remove("required");
+            if let Some(serde_json::Value::Object(properties)) = map.get_mut("properties") {
+                let appends: Vec<_> = properties
+                    .iter()
+                    .filter(|(_, property)| {
+                        property["type"] == "array"
+                            || property["type"]
+                                .as_array()
+                                .is_some_and(|types| types.contains(&"array".into()))
+                    })
+                    .map(|(key, property)| (format!("{key}+"), property.clone()))
+                    .collect();
+                properties.extend(appends);
+            }
+            map.values_mut().for_each(allow_layering);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(allow_layering),
+        _ => {}
+    }
+}
+
+/// The JSON Schema of the config format, for editor completion and validation.
+pub fn schema() -> String {
+    let mut schema = schemars::schema_for!(RawConfig).to_value();
+    allow_layering(&mut schema);
+    serde_json::to_string_pretty(&schema).unwrap_or_default()
 }
 
 impl TryFrom<RawConfig> for Config {
@@ -38,8 +249,21 @@ impl TryFrom<RawConfig> for Config {
 
     fn try_from(raw: RawConfig) -> Result<Self, Self::Error> {
         let mut services = Vec::with_capacity(raw.services.len());
+        let mut dependencies = Vec::with_capacity(raw.services.len());
 
-        for (index, raw_service) in raw.services.into_iter().enumerate() {
+        let basedir = match &raw.root {
+            Some(root) => root.clone(),
+            None => std::env::current_dir()?,
+        };
+        let vars = raw.vars.clone().unwrap_or_default();
+        let interpolator = Interpolator {
+            root: &basedir,
+            name: &raw.name,
+            vars: &vars,
+            services: &raw.services,
+        };
+
+        for (index, raw_service) in raw.services.iter().cloned().enumerate() {
             if raw_service.prog.trim().is_empty() {
                 anyhow::bail!(
                     "Service '{}' has no program defined",
@@ -47,9 +271,12 @@ impl TryFrom<RawConfig> for Config {
                 );
             }
 
-            let wdir = if let Some(raw_wdir) = raw_service.wdir {
+            let label = raw_service.name.clone().unwrap_or(raw_service.prog.clone());
+            let field = |field: &str| format!("'{}' {}", label, field);
+
+            let wdir = if let Some(raw_wdir) = &raw_service.wdir {
+                let raw_wdir = interpolator.resolve(raw_wdir, &field("wdir"))?;
                 let workdir = Path::new(&raw_wdir);
-                let basedir = std::env::current_dir().unwrap();
 
                 let resolved = if workdir.is_absolute() {
                     workdir.to_path_buf()
@@ -59,54 +286,288 @@ impl TryFrom<RawConfig> for Config {
 
                 resolved.clean().display().to_string()
             } else {
-                std::env::current_dir().unwrap().display().to_string()
+                basedir.display().to_string()
+            };
+
+            let prog = interpolator.resolve(&raw_service.prog, &field("prog"))?;
+
+            let mut args = raw_service.args.clone().unwrap_or(vec!());
+            for (index, arg) in args.iter_mut().enumerate() {
+                *arg = interpolator.resolve(arg, &field(&format!("args[{index}]")))?;
+            }
+
+            let mut env = raw_service.env.clone().unwrap_or_default();
+            for (key, value) in env.iter_mut() {
+                *value = interpolator.resolve(value, &field(&format!("env.{key}")))?;
+            }
+
+            let name = raw_service.name.unwrap_or(raw_service.prog);
+            let watch = match raw_service.watch {
+                Some(raw_watch) => Some(WatchConfig::from_raw(raw_watch, &name)?),
+                None => None,
             };
 
-            let prog = raw_service.prog.clone();
             let service = ServiceConfig {
                 wdir,
+                watch,
                 id: index.to_string(),
-                prog: raw_service.prog,
-                args: raw_service.args.unwrap_or(vec!()),
-                name: raw_service.name.unwrap_or(prog),
+                prog,
+                args,
+                name,
+                kind: raw_service.kind.unwrap_or_default(),
+                depends_on: vec![],
+                env,
+                hooks: HooksConfig {
+                    pre_start: raw_service.pre_start,
+                    post_start: raw_service.post_start,
+                    pre_stop: raw_service.pre_stop,
+                    post_stop: raw_service.post_stop,
+                },
+                autostart: raw_service.autostart.unwrap_or(true),
+                groups: raw_service.groups.unwrap_or_default(),
+                ports: raw_service.ports.unwrap_or_default(),
+                replicas: raw_service.replicas,
+                port_offset: raw_service.port_offset.unwrap_or(0),
+                on_port_conflict: raw_service.on_port_conflict,
             };
 
+            if let Some(count) = service.replicas {
+                service.check_replicas(count)?;
+            } else if service.port_offset != 0 {
+                anyhow::bail!("Service '{}' has a port_offset but no replicas", service.name);
+            }
+
             services.push(service);
+            dependencies.push(raw_service.depends_on.unwrap_or_default());
         }
 
+        // Dependencies are written as service names, resolve them into ids
+        for (index, names) in dependencies.into_iter().enumerate() {
+            let mut depends_on = Vec::with_capacity(names.len());
+            for name in names {
+                // Names default to the program, so several services may share one
+                let matches: Vec<&ServiceConfig> =
+                    services.iter().filter(|s| s.name == name).collect();
+                let dependency = match matches[..] {
+                    [dependency] => dependency,
+                    [] => anyhow::bail!(
+                        "Service '{}' depends on unknown service '{}'",
+                        services[index].name,
+                        name
+                    ),
+                    _ => {
+                        let commands: Vec<String> = matches
+                            .iter()
+                            .map(|s| {
+                                let words: Vec<&str> = std::iter::once(&s.prog)
+                                    .chain(&s.args)
+                                    .map(String::as_str)
+                                    .collect();
+                                words.join(" ")
+                            })
+                            .collect();
+                        anyhow::bail!(
+                            "Service '{}' depends on '{}', but several services have that name: {}",
+                            services[index].name,
+                            name,
+                            commands.join(", ")
+                        )
+                    }
+                };
+                if dependency.id == services[index].id {
+                    anyhow::bail!("Service '{}' depends on itself", name);
+                }
+                depends_on.push(dependency.id.clone());
+            }
+            services[index].depends_on = depends_on;
+        }
+
+        check_dependency_cycles(&services)?;
+
         Ok(Config {
             services,
             name: raw.name,
+            profiles: vec![],
+            detach: raw.detach.unwrap_or(true),
         })
     }
 }
 
-// Store in a static to access from anywhere, loaded lazily and once
-static CONFIG: OnceCell<Config> = OnceCell::new();
+// Instances a replicated service may run at most
+const MAX_REPLICAS: u32 = 100;
+
+impl ServiceConfig {
+    /// The config of a replicated service's instance, counting from 1. Its
+    /// ports are shifted by `port_offset` for each instance before it.
+    pub fn replica(&self, number: u32) -> ServiceConfig {
+        let index = number.saturating_sub(1);
+        let shift = index * u32::from(self.port_offset);
+
+        let mut replica = self.clone();
+        replica.id = format!("{}#{}", self.id, number);
+        replica.name = format!("{}#{}", self.name, number);
+        replica.replicas = None;
+        replica.ports = self
+            .ports
+            .iter()
+            .map(|port| u16::try_from(u32::from(*port) + shift).unwrap_or(u16::MAX))
+            .collect();
+        replica.env.insert("RUNZ_REPLICA".to_string(), replica.name.clone());
+        replica.env.insert("RUNZ_REPLICA_INDEX".to_string(), number.to_string());
+        replica.env.insert("RUNZ_PORT_OFFSET".to_string(), shift.to_string());
+        replica
+    }
 
-fn get_raw_config_from_argv() -> Option<String> {
-    let mut args = std::env::args().skip(1);
-    while let Some(arg) = args.next() {
-        if arg == "-r" || arg == "--raw-config" {
-            if let Some(raw) = args.next() {
-                return Some(raw);
+    /// Makes sure `count` replicas of the service can run side by side.
+    pub fn check_replicas(&self, count: u32) -> anyhow::Result<()> {
+        if count > MAX_REPLICAS {
+            anyhow::bail!(
+                "Service '{}' can't run {} replicas, the maximum is {}",
+                self.name,
+                count,
+                MAX_REPLICAS
+            );
+        }
+        if count > 1 && !self.ports.is_empty() && self.port_offset == 0 {
+            anyhow::bail!(
+                "Replicas of service '{}' would share its ports, set a port_offset",
+                self.name
+            );
+        }
+
+        let shift = count.saturating_sub(1) * u32::from(self.port_offset);
+        if let Some(port) = self.ports.iter().find(|port| u32::from(**port) + shift > 65535) {
+            anyhow::bail!(
+                "Service '{}' can't run {} replicas, port {} would go past 65535",
+                self.name,
+                count,
+                port
+            );
+        }
+        Ok(())
+    }
+}
+
+fn check_dependency_cycles(services: &[ServiceConfig]) -> anyhow::Result<()> {
+    fn visit<'a>(
+        service: &'a ServiceConfig,
+        services: &'a [ServiceConfig],
+        path: &mut Vec<&'a str>,
+        done: &mut Vec<&'a str>,
+    ) -> anyhow::Result<()> {
+        if done.contains(&service.id.as_str()) {
+            return Ok(());
+        }
+        if path.contains(&service.id.as_str()) {
+            anyhow::bail!("Service '{}' has a circular dependency", service.name);
+        }
+
+        path.push(&service.id);
+        for id in &service.depends_on {
+            if let Some(dependency) = services.iter().find(|s| &s.id == id) {
+                visit(dependency, services, path, done)?;
             }
         }
+        path.pop();
+        done.push(&service.id);
+
+        Ok(())
     }
-    None
+
+    let mut done = vec![];
+    for service in services {
+        visit(service, services, &mut vec![], &mut done)?;
+    }
+    Ok(())
 }
 
-fn get_config_path_from_argv() -> Option<PathBuf> {
-    let mut args = std::env::args().skip(1);
+impl Config {
+    /// Where the logs and the control socket of the workspace live.
+    pub fn tempdir(&self) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("runz/{}", slugify!(&self.name)))
+            .clean()
+    }
+
+    pub fn find_service(&self, id: &str) -> Option<&ServiceConfig> {
+        self.services.iter().find(|s| s.id == id)
+    }
 
-    while let Some(arg) = args.next() {
-        if arg == "-c" || arg == "--config" {
-            if let Some(path) = args.next() {
-                return Some(PathBuf::from(path));
+    /// Every group used by a service, in order of first appearance.
+    pub fn groups(&self) -> Vec<&str> {
+        let mut groups: Vec<&str> = vec![];
+        for group in self.services.iter().flat_map(|s| &s.groups) {
+            if !groups.contains(&group.as_str()) {
+                groups.push(group);
             }
         }
+        groups
+    }
+
+    /// Restricts autostart to the services belonging to one of the profiles.
+    /// Services outside of them are still listed and can be started by hand.
+    fn activate_profiles(&mut self, profiles: Vec<String>) -> anyhow::Result<()> {
+        if profiles.is_empty() {
+            return Ok(());
+        }
+
+        let groups = self.groups();
+        if let Some(unknown) = profiles.iter().find(|p| !groups.contains(&p.as_str())) {
+            anyhow::bail!(
+                "Unknown profile '{}'. Available groups: {}",
+                unknown,
+                groups.join(", ")
+            );
+        }
+
+        for service in self.services.iter_mut() {
+            if !service.groups.iter().any(|g| profiles.contains(g)) {
+                service.autostart = false;
+            }
+        }
+        self.profiles = profiles;
+
+        Ok(())
+    }
+
+    /// Services sorted so that each one comes after everything it depends on,
+    /// keeping the config order otherwise.
+    pub fn dependency_order(&self) -> Vec<&ServiceConfig> {
+        let mut ordered: Vec<&ServiceConfig> = Vec::with_capacity(self.services.len());
+
+        while ordered.len() < self.services.len() {
+            let before = ordered.len();
+            for service in &self.services {
+                let placed = ordered.iter().any(|s| s.id == service.id);
+                let ready = service
+                    .depends_on
+                    .iter()
+                    .all(|id| ordered.iter().any(|s| &s.id == id));
+                if !placed && ready {
+                    ordered.push(service);
+                }
+            }
+
+            // Cycles are rejected when loading, but never loop forever
+            if ordered.len() == before {
+                break;
+            }
+        }
+
+        ordered
     }
-    None
+}
+
+// Store in a static to access from anywhere, loaded lazily and once
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+fn get_profiles_from_argv() -> Vec<String> {
+    cli::args()
+        .profile
+        .iter()
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect()
 }
 
 fn get_config_path_from_env() -> Option<PathBuf> {
@@ -116,51 +577,78 @@ fn get_config_path_from_env() -> Option<PathBuf> {
     None
 }
 
+const CONFIG_FILES: [&str; 5] = [
+    "runz.yaml",
+    "runz.yml",
+    "runz.json",
+    "runz.jsonc",
+    "runz.toml",
+];
+
+/// Finds the config file from argv, the environment, or the current
+/// directory and its parents, up to the git root.
+/// Like the other messages of config loading, what it found goes to stderr,
+/// leaving stdout to commands such as `runz ctl status --json`.
 fn find_config_path() -> Option<PathBuf> {
-    if let Some(config_path) = get_config_path_from_argv() {
-        println!("Using config path from command argv");
-        return Some(config_path);
+    if let Some(config_path) = &cli::args().config {
+        eprintln!("Using config path from command argv");
+        return Some(config_path.clone());
     }
 
     if let Some(config_path) = get_config_path_from_env() {
-        println!("Using config path from RUNZ_CONFIG environment variable");
+        eprintln!("Using config path from RUNZ_CONFIG environment variable");
         return Some(config_path);
     }
 
-    let default_candidates = vec![
-        "runz.yaml",
-        "runz.yml",
-        "runz.json",
-        "runz.jsonc",
-        "runz.toml",
-    ];
+    // Like git and cargo, don't look outside of the repository
+    let cwd = std::env::current_dir().ok()?;
+    let mut dirs = vec![];
+    for dir in cwd.ancestors() {
+        dirs.push(dir);
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    for dir in &dirs {
+        for file in CONFIG_FILES {
+            let p = dir.join(file);
+            if p.exists() {
+                eprintln!("Using config file: {}", p.display());
+                return Some(p);
+            }
+        }
+    }
 
-    for file in default_candidates {
-        let p = std::env::current_dir().ok()?.join(file);
+    // Fall back to a Procfile, one service per line, only when no directory
+    // has a config file
+    for dir in &dirs {
+        let p = dir.join("Procfile");
         if p.exists() {
-            println!("Using config file: {}", p.display());
+            eprintln!("Using Procfile: {}", p.display());
             return Some(p);
         }
     }
     None
 }
 
-fn parse_config(config_type: &str, config_data: &str) -> anyhow::Result<Config> {
-    let raw_config: RawConfig;
-    match config_type {
-        "yaml" | "yml" => {
-            raw_config = serde_yaml::from_str(&config_data)?;
-        }
-        "json" | "jsonc" => {
-            raw_config = serde_json::from_str(&config_data)?;
-        }
-        "toml" => {
-            raw_config = toml::from_str(&config_data)?;
-        }
+/// Parses a single config document, in any of the supported formats.
+pub fn parse_value(config_type: &str, config_data: &str) -> anyhow::Result<serde_json::Value> {
+    let value = match config_type {
+        "yaml" | "yml" => serde_yaml::from_str(config_data)?,
+        "json" | "jsonc" => serde_json::from_str(config_data)?,
+        "toml" => toml::from_str(config_data)?,
         _ => {
             anyhow::bail!("Unsupported file format. Expected .yaml, .yml, .json, .jsonc, or .toml")
         }
-    }
+    };
+
+    Ok(value)
+}
+
+fn build_config(layered: Layered) -> anyhow::Result<Config> {
+    let mut raw_config: RawConfig = layered.deserialize()?;
+    raw_config.root = layered.root;
 
     if raw_config.services.is_empty() {
         anyhow::bail!("No services defined in config");
@@ -173,29 +661,182 @@ fn parse_config(config_type: &str, config_data: &str) -> anyhow::Result<Config>
     Ok(Config::try_from(raw_config)?)
 }
 
-pub fn get_config() -> anyhow::Result<&'static Config> {
-    CONFIG.get_or_try_init(|| {
-        // Try loading via arguments directly
-        if let Some(raw_config_from_argv) = get_raw_config_from_argv() {
-            println!("Using inline config from command line argv");
-            if let Some((config_type, config_data)) = raw_config_from_argv.split_once('|') {
-                return Ok(parse_config(config_type.trim(), config_data.trim().trim_matches('"'))?);
-            } else {
-                anyhow::bail!("Invalid CLI config input format. Expected <format>|<config_data>");
-            }
+fn parse_config(config_type: &str, config_data: &str) -> anyhow::Result<Config> {
+    build_config(layers::load_str(config_type, config_data)?)
+}
+
+fn load_config() -> anyhow::Result<Config> {
+    // Try loading via arguments directly
+    if let Some(raw_config_from_argv) = &cli::args().raw_config {
+        eprintln!("Using inline config from command line argv");
+        if let Some((config_type, config_data)) = raw_config_from_argv.split_once('|') {
+            return Ok(parse_config(config_type.trim(), config_data.trim().trim_matches('"'))?);
+        } else {
+            anyhow::bail!("Invalid CLI config input format. Expected <format>|<config_data>");
         }
+    }
 
-        // Try finding config file from fs
-        if let Some(config_path) = find_config_path() {
-            let config_type = config_path
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .unwrap_or("toml");
+    // Try finding config file from fs
+    if let Some(config_path) = find_config_path() {
+        return build_config(layers::load_file(&config_path)?);
+    }
 
-            let config_data = std::fs::read_to_string(&config_path)?;
-            return Ok(parse_config(config_type, &config_data)?);
-        }
+    anyhow::bail!("No config provided and no config file found");
+}
 
-        anyhow::bail!("No config provided and no config file found");
+pub fn get_config() -> anyhow::Result<&'static Config> {
+    CONFIG.get_or_try_init(|| {
+        let mut config = load_config()?;
+        config.activate_profiles(get_profiles_from_argv())?;
+        Ok(config)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(yaml: &str) -> anyhow::Result<Config> {
+        let mut raw: RawConfig = serde_json::from_value(parse_value("yaml", yaml)?)?;
+        raw.root = Some(PathBuf::from("/work"));
+        Config::try_from(raw)
+    }
+
+    #[test]
+    fn resolves_dependencies_by_name() {
+        let config = load(
+            "
+name: Shop
+services:
+  - name: api
+    prog: go
+    depends_on: [db, migrate]
+  - name: db
+    prog: postgres
+  - name: migrate
+    prog: sqlx
+    kind: task
+    depends_on: [db]
+",
+        )
+        .unwrap();
+
+        assert_eq!(config.services[0].depends_on, ["1", "2"]);
+        assert_eq!(config.services[2].depends_on, ["1"]);
+    }
+
+    #[test]
+    fn rejects_bad_dependencies() {
+        let api = |depends_on: &str| {
+            load(&format!(
+                "name: Shop\nservices:\n  - name: api\n    prog: go\n    depends_on: [{depends_on}]\n"
+            ))
+        };
+        let err = api("db").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Service 'api' depends on unknown service 'db'"
+        );
+        let err = api("api").unwrap_err();
+        assert_eq!(err.to_string(), "Service 'api' depends on itself");
+
+        let err = load(
+            "
+name: Shop
+services:
+  - prog: redis-server
+  - prog: redis-server
+    args: [--port, '6380']
+  - name: api
+    prog: go
+    depends_on: [redis-server]
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Service 'api' depends on 'redis-server', but several services have that name: \
+             redis-server, redis-server --port 6380"
+        );
+
+        let err = load(
+            "
+name: Shop
+services:
+  - name: a
+    prog: x
+    depends_on: [b]
+  - name: b
+    prog: x
+    depends_on: [c]
+  - name: c
+    prog: x
+    depends_on: [a]
+",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("circular dependency"), "{err}");
+    }
+
+    #[test]
+    fn orders_services_after_their_dependencies() {
+        let config = load(
+            "
+name: Shop
+services:
+  - name: web
+    prog: npm
+    depends_on: [api]
+  - name: worker
+    prog: celery
+  - name: api
+    prog: go
+    depends_on: [db]
+  - name: db
+    prog: postgres
+",
+        )
+        .unwrap();
+
+        let order: Vec<&str> = config
+            .dependency_order()
+            .iter()
+            .map(|service| service.name.as_str())
+            .collect();
+        assert_eq!(order, ["worker", "db", "api", "web"]);
+    }
+
+    fn replicated(ports: &str, port_offset: u16) -> ServiceConfig {
+        let yaml = format!(
+            "name: Shop\nservices:\n  - name: api\n    prog: go\n    ports: [{ports}]\n    port_offset: {port_offset}\n    replicas: 1\n"
+        );
+        load(&yaml).unwrap().services.remove(0)
+    }
+
+    #[test]
+    fn shifts_the_ports_of_each_replica() {
+        let replica = replicated("8080, 9090", 10).replica(3);
+
+        assert_eq!(replica.id, "0#3");
+        assert_eq!(replica.name, "api#3");
+        assert_eq!(replica.replicas, None);
+        assert_eq!(replica.ports, [8100, 9110]);
+        assert_eq!(replica.env["RUNZ_REPLICA_INDEX"], "3");
+        assert_eq!(replica.env["RUNZ_PORT_OFFSET"], "20");
+    }
+
+    #[test]
+    fn checks_replica_counts() {
+        let service = replicated("8080", 10);
+        assert!(service.check_replicas(0).is_ok());
+        assert!(service.check_replicas(MAX_REPLICAS).is_ok());
+        assert!(service.check_replicas(MAX_REPLICAS + 1).is_err());
+
+        let err = replicated("65530", 10).check_replicas(2).unwrap_err();
+        assert!(err.to_string().contains("past 65535"), "{err}");
+
+        let err = replicated("8080", 0).check_replicas(2).unwrap_err();
+        assert!(err.to_string().contains("set a port_offset"), "{err}");
+        assert!(replicated("", 0).check_replicas(2).is_ok());
+    }
+}
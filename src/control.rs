@@ -0,0 +1,362 @@
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tiny_http::{Method, Response, Server};
+
+use crate::cli::CtlAction;
+use crate::cmd;
+use crate::config::{self, Config, ServiceConfig};
+use crate::endpoints;
+use crate::interpolate::service_key;
+use crate::ports;
+use crate::pty_manager;
+use crate::replicas;
+use crate::stats;
+
+// How often `runz ctl status --wait-ready` checks on the services
+const WAIT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The socket `runz ctl` talks to, in the workspace's temp directory.
+pub fn socket_path(tempdir: &Path) -> PathBuf {
+    tempdir.join("control.sock")
+}
+
+fn is_named(service: &ServiceConfig, name: &str) -> bool {
+    service.id == name || service_key(&service.name) == service_key(name)
+}
+
+// Finds a service by name (compared like `${services.<name>}` keys) or id
+fn find_service<'a>(config: &'a Config, name: &str) -> Result<&'a ServiceConfig, String> {
+    config
+        .services
+        .iter()
+        .find(|s| is_named(s, name))
+        .ok_or_else(|| format!("Unknown service '{}'", name))
+}
+
+// Finds what a name stands for: a service, each replica of a replicated
+// service, or a single replica such as `worker#2`
+fn find_instances(config: &Config, name: &str) -> Result<Vec<ServiceConfig>, String> {
+    if let Ok(service) = find_service(config, name) {
+        return Ok(replicas::instances(service));
+    }
+    replicas::expand(config)
+        .into_iter()
+        .find(|s| is_named(s, name))
+        .map(|s| vec![s])
+        .ok_or_else(|| format!("Unknown service '{}'", name))
+}
+
+// Runs a command on everything a name stands for
+fn for_each(
+    config: &Config,
+    name: &str,
+    command: impl Fn(String) -> Result<(), String>,
+) -> Result<(), String> {
+    find_instances(config, name)?
+        .into_iter()
+        .try_for_each(|s| command(s.id))
+}
+
+fn list_services(config: &Config) -> String {
+    let services: Vec<_> = replicas::expand(config)
+        .iter()
+        .map(|s| {
+            json!({
+                "id": s.id,
+                "name": s.name,
+                "state": pty_manager::run_state(&s.id).name(),
+            })
+        })
+        .collect();
+    json!(services).to_string()
+}
+
+fn status(config: &Config) -> String {
+    let snapshot = stats::Snapshot::take();
+    let sockets = endpoints::listening_sockets();
+    let services: Vec<_> = replicas::expand(config)
+        .iter()
+        .map(|s| {
+            let status = pty_manager::status(&s.id);
+            json!({
+                "id": s.id,
+                "name": s.name,
+                "kind": s.kind,
+                "state": status.state.name(),
+                "pid": status.pid,
+                "ready": ports::is_ready(s, &snapshot, &sockets),
+                "exit_code": status.exit_code,
+                "restarts": status.restarts,
+                "endpoints": endpoints::get(&s.id),
+            })
+        })
+        .collect();
+    json!(services).to_string()
+}
+
+fn handle(method: &Method, url: &str, body: &str) -> Result<String, String> {
+    let config = config::get_config().map_err(|e| e.to_string())?;
+
+    if *method == Method::Get && url == "/services" {
+        return Ok(list_services(config));
+    }
+    if *method == Method::Get && url == "/status" {
+        return Ok(status(config));
+    }
+    if *method != Method::Post {
+        return Err(format!("Unknown request {} {}", method, url));
+    }
+
+    match url {
+        "/start" => for_each(config, body, cmd::start_terminal),
+        "/stop" => for_each(config, body, cmd::stop_terminal),
+        "/restart" => for_each(config, body, cmd::restart_terminal),
+        "/pause" => for_each(config, body, cmd::pause_terminal),
+        "/resume" => for_each(config, body, cmd::resume_terminal),
+        "/start-all" => cmd::start_all_terminals(),
+        "/stop-all" => cmd::stop_all_terminals(),
+        "/restart-all" => cmd::restart_all_terminals(),
+        "/start-group" if config.groups().contains(&body) => {
+            cmd::start_group_terminals(body.to_string())
+        }
+        "/start-group" => Err(format!("Unknown group '{}'", body)),
+        _ if url.starts_with("/signal/") => {
+            let signal = url.trim_start_matches("/signal/");
+            for_each(config, body, |id| {
+                cmd::signal_terminal(id, signal.to_string())
+            })
+        }
+        _ if url.starts_with("/scale/") => {
+            let replicas = url
+                .trim_start_matches("/scale/")
+                .parse()
+                .map_err(|_| format!("Invalid replica count in {}", url))?;
+            cmd::scale_terminal(find_service(config, body)?.id.clone(), replicas)
+        }
+        _ => Err(format!("Unknown request {} {}", method, url)),
+    }?;
+
+    Ok(String::new())
+}
+
+/// Listens for `runz ctl` requests on the workspace's control socket.
+pub fn serve(tempdir: &Path) -> anyhow::Result<()> {
+    let path = socket_path(tempdir);
+
+    // A socket left behind by a crashed run can be replaced, a live one can't
+    if UnixStream::connect(&path).is_ok() {
+        anyhow::bail!("Another runz is already running this workspace");
+    }
+    let _ = std::fs::remove_file(&path);
+
+    let server = Server::http_unix(&path).map_err(|e| anyhow::anyhow!("{}", e))?;
+    std::thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let mut body = String::new();
+            let _ = request.as_reader().read_to_string(&mut body);
+
+            let response = match handle(request.method(), request.url(), body.trim()) {
+                Ok(reply) => Response::from_string(reply),
+                Err(e) => Response::from_string(e).with_status_code(400),
+            };
+            let _ = request.respond(response);
+        }
+    });
+
+    Ok(())
+}
+
+fn request(tempdir: &Path, method: &str, url: &str, body: &str) -> anyhow::Result<String> {
+    let path = socket_path(tempdir);
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|_| anyhow::anyhow!("runz is not running for this workspace"))?;
+
+    write!(
+        stream,
+        "{method} {url} HTTP/1.0\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+
+    let (head, reply) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    if head.split_whitespace().nth(1) != Some("200") {
+        anyhow::bail!("{}", reply.trim());
+    }
+    Ok(reply.to_string())
+}
+
+fn print_status(services: &[Value]) {
+    let text = |service: &Value, key: &str| match &service[key] {
+        Value::Null => "-".to_string(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    };
+    let endpoints = |service: &Value| {
+        let urls = service["endpoints"]["urls"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let ports = service["endpoints"]["ports"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let all: Vec<String> = urls
+            .iter()
+            .filter_map(|url| url.as_str().map(str::to_string))
+            .chain(ports.iter().map(|port| format!(":{port}")))
+            .collect();
+        if all.is_empty() {
+            "-".to_string()
+        } else {
+            all.join(" ")
+        }
+    };
+
+    let rows: Vec<[String; 6]> = services
+        .iter()
+        .map(|s| {
+            [
+                text(s, "name"),
+                text(s, "state"),
+                if s["ready"] == true { "yes" } else { "no" }.to_string(),
+                text(s, "pid"),
+                text(s, "exit_code"),
+                endpoints(s),
+            ]
+        })
+        .collect();
+    let header = ["NAME", "STATE", "READY", "PID", "EXIT", "ENDPOINTS"].map(str::to_string);
+
+    let mut widths = [0; 6];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+// Shows the status of the given services, or all of them. With `wait`, keeps
+// asking until they are all ready, failing once it ran out.
+fn show_status(
+    config: &Config,
+    tempdir: &Path,
+    names: &[String],
+    json_output: bool,
+    wait: Option<Duration>,
+) -> anyhow::Result<()> {
+    let mut ids = vec![];
+    for name in names {
+        let instances = find_instances(config, name).map_err(|e| anyhow::anyhow!(e))?;
+        ids.extend(instances.into_iter().map(|s| s.id));
+    }
+    let deadline = wait.map(|timeout| Instant::now() + timeout);
+
+    loop {
+        let reply: Value = serde_json::from_str(&request(tempdir, "GET", "/status", "")?)?;
+        let services: Vec<Value> = reply
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|s| ids.is_empty() || ids.iter().any(|id| s["id"] == id.as_str()))
+            .collect();
+        let waiting: Vec<String> = services
+            .iter()
+            .filter(|s| s["ready"] != true)
+            .map(|s| {
+                format!(
+                    "{} ({})",
+                    s["name"].as_str().unwrap_or_default(),
+                    s["state"].as_str().unwrap_or_default()
+                )
+            })
+            .collect();
+
+        let timed_out = deadline.is_some_and(|deadline| Instant::now() >= deadline);
+        if deadline.is_none() || waiting.is_empty() || timed_out {
+            if json_output {
+                println!("{}", serde_json::to_string_pretty(&services)?);
+            } else {
+                print_status(&services);
+            }
+            if timed_out && !waiting.is_empty() {
+                anyhow::bail!("Timed out waiting for {}", waiting.join(", "));
+            }
+            return Ok(());
+        }
+        std::thread::sleep(WAIT_INTERVAL);
+    }
+}
+
+/// Runs `runz ctl` against the workspace of the current config.
+pub fn run(action: &CtlAction) -> anyhow::Result<()> {
+    let config = config::get_config()?;
+    let tempdir = config.tempdir();
+
+    let (url, names) = match action {
+        CtlAction::List => {
+            let reply: serde_json::Value =
+                serde_json::from_str(&request(&tempdir, "GET", "/services", "")?)?;
+            let services = reply.as_array().cloned().unwrap_or_default();
+            let width = services
+                .iter()
+                .filter_map(|s| s["name"].as_str())
+                .map(str::len)
+                .max()
+                .unwrap_or(0);
+
+            for service in services {
+                let name = service["name"].as_str().unwrap_or_default();
+                let state = service["state"].as_str().unwrap_or_default();
+                println!("{:width$}  {}", name, state, width = width);
+            }
+            return Ok(());
+        }
+        CtlAction::Status {
+            services,
+            json,
+            wait_ready,
+            timeout,
+        } => {
+            let wait = wait_ready.then(|| Duration::from_secs(*timeout));
+            return show_status(config, &tempdir, services, *json, wait);
+        }
+        CtlAction::Scale { service, replicas } => {
+            request(&tempdir, "POST", &format!("/scale/{replicas}"), service)?;
+            return Ok(());
+        }
+        CtlAction::Signal { signal, services } => {
+            for name in services {
+                request(&tempdir, "POST", &format!("/signal/{signal}"), name)?;
+            }
+            return Ok(());
+        }
+        CtlAction::Start { services } => ("/start", services.clone()),
+        CtlAction::Stop { services } => ("/stop", services.clone()),
+        CtlAction::Restart { services } => ("/restart", services.clone()),
+        CtlAction::Pause { services } => ("/pause", services.clone()),
+        CtlAction::Resume { services } => ("/resume", services.clone()),
+        CtlAction::StartAll => ("/start-all", vec![String::new()]),
+        CtlAction::StopAll => ("/stop-all", vec![String::new()]),
+        CtlAction::RestartAll => ("/restart-all", vec![String::new()]),
+        CtlAction::StartGroup { group } => ("/start-group", vec![group.clone()]),
+    };
+
+    for name in names {
+        request(&tempdir, "POST", url, &name)?;
+    }
+    Ok(())
+}
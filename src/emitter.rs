@@ -1,14 +1,33 @@
 use once_cell::sync::OnceCell;
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::Mutex;
 
 static EMITTER: OnceCell<Mutex<Sender<(String, serde_json::Value)>>> = OnceCell::new();
 
+// Anyone else interested in events, such as remote browsers
+static SUBSCRIBERS: Mutex<Vec<Sender<(String, serde_json::Value)>>> = Mutex::new(Vec::new());
+
 pub fn set_emitter(sender: &Sender<(String, serde_json::Value)>) {
     let _ = EMITTER.set(Mutex::new(sender.clone()));
 }
 
+/// Whether the window's frontend receives events.
+pub fn has_window() -> bool {
+    EMITTER.get().is_some()
+}
+
+/// Receives a copy of every event emitted from now on.
+pub fn subscribe() -> Receiver<(String, serde_json::Value)> {
+    let (tx, rx) = mpsc::channel();
+    SUBSCRIBERS.lock().unwrap().push(tx);
+    rx
+}
+
 pub fn emit(event: String, data: serde_json::Value) {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    subscribers.retain(|subscriber| subscriber.send((event.clone(), data.clone())).is_ok());
+    drop(subscribers);
+
     if let Some(lock) = EMITTER.get() {
         let _ = lock.lock().unwrap().send((event, data));
     }
@@ -0,0 +1,314 @@
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{emitter, pty_manager, stats::Snapshot};
+
+// How often listening sockets are looked up
+const INTERVAL: Duration = Duration::from_secs(2);
+
+// Output without a line break is scanned anyway once it gets this long
+const MAX_PENDING: usize = 4096;
+
+/// Where a service can be reached: URLs it printed and ports it listens on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Endpoints {
+    pub urls: Vec<String>,
+    pub ports: Vec<u16>,
+}
+
+lazy_static::lazy_static! {
+    static ref ENDPOINTS: Mutex<HashMap<String, Endpoints>> = Mutex::new(HashMap::new());
+}
+
+// Applies a change to a service's endpoints, notifying the frontend if it
+// changed anything
+fn update(id: &str, change: impl FnOnce(&mut Endpoints)) {
+    let mut all = ENDPOINTS.lock();
+    let endpoints = all.entry(id.to_string()).or_default();
+    let before = endpoints.clone();
+    change(endpoints);
+
+    if *endpoints != before {
+        emitter::emit(
+            id.to_string(),
+            serde_json::json!({ "type": "endpoints", "data": endpoints }),
+        );
+    }
+}
+
+/// What is known about where a service can be reached.
+pub fn get(id: &str) -> Endpoints {
+    ENDPOINTS.lock().get(id).cloned().unwrap_or_default()
+}
+
+/// Forgets everything known about a service, e.g. before it's spawned again.
+pub fn reset(id: &str) {
+    update(id, |endpoints| *endpoints = Endpoints::default());
+}
+
+// Drops terminal escape sequences, which dev servers use to highlight URLs
+fn strip_ansi(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            // CSI, ends with a byte in `@`..=`~`
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+            // OSC, ends with BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    output
+}
+
+// Keeps URLs pointing at this machine, with an explicit port, showing
+// wildcard hosts as localhost
+fn local_url(url: &str) -> Option<String> {
+    let rest = url
+        .strip_prefix("http://")
+        .or_else(|| url.strip_prefix("https://"))?;
+    let scheme = &url[..url.len() - rest.len()];
+
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let (host, port) = authority.rsplit_once(':')?;
+    port.parse::<u16>().ok()?;
+
+    let host = match host {
+        "0.0.0.0" | "[::]" => "localhost",
+        "localhost" | "[::1]" => host,
+        _ if host.starts_with("127.") || host.ends_with(".localhost") => host,
+        _ => return None,
+    };
+    Some(format!("{scheme}{host}:{port}{path}"))
+}
+
+fn find_urls(text: &str) -> Vec<String> {
+    let mut urls = vec![];
+    let mut rest = text;
+
+    while let Some(start) = rest.find("http") {
+        let candidate = &rest[start..];
+        let end = candidate
+            .find(|c: char| c.is_whitespace() || "\"'<>()[]`".contains(c))
+            .unwrap_or(candidate.len());
+        let url = candidate[..end].trim_end_matches(['.', ',', ';', ':']);
+
+        if let Some(url) = local_url(url) {
+            urls.push(url);
+        }
+        rest = &candidate[end.max(4)..];
+    }
+    urls
+}
+
+/// Picks local URLs out of a service's output as it streams by.
+pub struct OutputScanner {
+    id: String,
+    // Output after the last line break, a URL may continue in the next chunk
+    pending: String,
+}
+
+impl OutputScanner {
+    pub fn new(id: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            pending: String::new(),
+        }
+    }
+
+    pub fn feed(&mut self, output: &str) {
+        self.pending.push_str(output);
+
+        let complete = match self.pending.rfind('\n') {
+            Some(index) => index + 1,
+            None if self.pending.len() > MAX_PENDING => self.pending.len(),
+            None => return,
+        };
+        let text: String = self.pending.drain(..complete).collect();
+
+        let urls = find_urls(&strip_ansi(&text));
+        if !urls.is_empty() {
+            update(&self.id, |endpoints| {
+                for url in urls {
+                    if !endpoints.urls.contains(&url) {
+                        endpoints.urls.push(url);
+                    }
+                }
+            });
+        }
+    }
+}
+
+/// Inodes of the listening TCP sockets, with their port.
+#[cfg(target_os = "linux")]
+pub fn listening_sockets() -> HashMap<u64, u16> {
+    let mut sockets = HashMap::new();
+
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let Ok(data) = std::fs::read_to_string(table) else {
+            continue;
+        };
+        for line in data.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // 0A is TCP_LISTEN
+            if fields.get(3) != Some(&"0A") {
+                continue;
+            }
+            let port = fields
+                .get(1)
+                .and_then(|address| address.rsplit_once(':'))
+                .and_then(|(_, port)| u16::from_str_radix(port, 16).ok());
+            let inode = fields.get(9).and_then(|inode| inode.parse::<u64>().ok());
+            if let (Some(port), Some(inode)) = (port, inode) {
+                sockets.insert(inode, port);
+            }
+        }
+    }
+    sockets
+}
+
+// Inodes of the sockets a process has open, from its fd table
+#[cfg(target_os = "linux")]
+fn socket_inodes(pid: u32) -> Vec<u64> {
+    let Ok(entries) = std::fs::read_dir(format!("/proc/{pid}/fd")) else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| {
+            let target = std::fs::read_link(entry.ok()?.path()).ok()?;
+            let inode = target.to_str()?.strip_prefix("socket:[")?.strip_suffix(']')?;
+            inode.parse().ok()
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn listening_sockets() -> HashMap<u64, u16> {
+    HashMap::new()
+}
+
+/// Ports `root` or anything it spawned listens on, among `sockets`. Looking
+/// several services up in the same snapshot and socket table reads /proc once.
+#[cfg(target_os = "linux")]
+pub fn listening_ports(root: u32, snapshot: &Snapshot, sockets: &HashMap<u64, u16>) -> Vec<u16> {
+    let mut ports: Vec<u16> = snapshot
+        .tree(root)
+        .into_iter()
+        .flat_map(socket_inodes)
+        .filter_map(|inode| sockets.get(&inode).copied())
+        .collect();
+    ports.sort_unstable();
+    ports.dedup();
+    ports
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn listening_ports(_root: u32, _snapshot: &Snapshot, _sockets: &HashMap<u64, u16>) -> Vec<u16> {
+    vec![]
+}
+
+/// The process listening on `port`, if it can be seen. Processes of other
+/// users keep their fd table to themselves.
+#[cfg(target_os = "linux")]
+pub fn port_owner(port: u16) -> Option<u32> {
+    let inodes: Vec<u64> = listening_sockets()
+        .into_iter()
+        .filter(|(_, listening)| *listening == port)
+        .map(|(inode, _)| inode)
+        .collect();
+    if inodes.is_empty() {
+        return None;
+    }
+
+    std::fs::read_dir("/proc")
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .find(|pid| socket_inodes(*pid).iter().any(|inode| inodes.contains(inode)))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn port_owner(_port: u16) -> Option<u32> {
+    None
+}
+
+/// Keeps the listening ports of every running service up to date, and
+/// clears the endpoints of services that stopped.
+pub fn start() {
+    std::thread::spawn(|| loop {
+        std::thread::sleep(INTERVAL);
+
+        let sessions = pty_manager::sessions();
+        let snapshot = Snapshot::take();
+        let sockets = listening_sockets();
+        for session in &sessions {
+            let ports = session
+                .pid
+                .map(|pid| listening_ports(pid, &snapshot, &sockets))
+                .unwrap_or_default();
+            update(&session.id, |endpoints| endpoints.ports = ports);
+        }
+
+        let known: Vec<String> = ENDPOINTS.lock().keys().cloned().collect();
+        for id in known {
+            if !pty_manager::is_running(&id) {
+                reset(&id);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_escape_sequences() {
+        assert_eq!(
+            strip_ansi("\x1b[1m\x1b[32mLocal\x1b[0m:   http://localhost:\x1b[1m5173\x1b[22m/"),
+            "Local:   http://localhost:5173/"
+        );
+        // A hyperlink, its target hidden behind the text
+        assert_eq!(
+            strip_ansi("\x1b]8;;http://example.com\x1b\\docs\x1b]8;;\x07 ready"),
+            "docs ready"
+        );
+    }
+
+    #[test]
+    fn finds_local_urls() {
+        assert_eq!(
+            find_urls("Listening on http://0.0.0.0:8080, docs at (http://127.0.0.1:8080/docs)."),
+            ["http://localhost:8080", "http://127.0.0.1:8080/docs"]
+        );
+        assert_eq!(
+            find_urls("ready: https://app.localhost:3443/login\n"),
+            ["https://app.localhost:3443/login"]
+        );
+    }
+
+    #[test]
+    fn skips_remote_urls_and_urls_without_a_port() {
+        assert!(find_urls("see https://example.com:443/ and http://localhost/").is_empty());
+        assert!(find_urls("http is up, httpd too").is_empty());
+    }
+}
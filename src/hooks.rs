@@ -0,0 +1,188 @@
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Sender};
+use std::time::{Duration, Instant};
+
+use crate::{
+    config::{ServiceConfig, ServiceKind},
+    emitter, endpoints, ports,
+    pty_manager::{self, RunState},
+    stats::Snapshot,
+};
+
+// How long a hook's output is waited for once the hook exited. Something it
+// left running in the background may hold on to it for good.
+const OUTPUT_TIMEOUT: Duration = Duration::from_secs(1);
+
+// How long post_start waits for a service to listen on its ports
+const READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    PreStart,
+    PostStart,
+    PreStop,
+    PostStop,
+}
+
+impl Hook {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hook::PreStart => "pre_start",
+            Hook::PostStart => "post_start",
+            Hook::PreStop => "pre_stop",
+            Hook::PostStop => "post_stop",
+        }
+    }
+
+    fn command<'a>(&self, service: &'a ServiceConfig) -> Option<&'a str> {
+        let hooks = &service.hooks;
+        match self {
+            Hook::PreStart => hooks.pre_start.as_deref(),
+            Hook::PostStart => hooks.post_start.as_deref(),
+            Hook::PreStop => hooks.pre_stop.as_deref(),
+            Hook::PostStop => hooks.post_stop.as_deref(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+// Forwards every line of a hook stream into the service's terminal, tagged
+// with the hook name. `done` is dropped once the stream ends.
+fn forward_output(id: String, hook: Hook, stream: impl Read + Send + 'static, done: Sender<()>) {
+    std::thread::spawn(move || {
+        let _done = done;
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            emitter::emit(
+                id.clone(),
+                serde_json::json!({
+                    "type": "output",
+                    "data": format!("\x1b[2;36m[{}]\x1b[0m {}\r\n", hook.name(), line),
+                }),
+            );
+        }
+    });
+}
+
+/// Runs a service's hook, if it has one, in the service's working directory
+/// and environment. Blocks until the hook exits and fails when it exits
+/// with a non-zero code. Output of anything the hook left running in the
+/// background keeps being forwarded.
+pub fn run_hook(service: &ServiceConfig, hook: Hook) -> Result<(), String> {
+    let Some(command) = hook.command(service) else {
+        return Ok(());
+    };
+
+    let mut child = shell(command)
+        .current_dir(&service.wdir)
+        .env("PWD", &service.wdir)
+        .envs(&service.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{} hook failed to start: {e}", hook.name()))?;
+
+    let (done, finished) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        forward_output(service.id.clone(), hook, stdout, done.clone());
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forward_output(service.id.clone(), hook, stderr, done);
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("{} hook failed: {e}", hook.name()))?;
+
+    // Nothing is sent, this returns once both streams ended or on timeout
+    let _ = finished.recv_timeout(OUTPUT_TIMEOUT);
+
+    if !status.success() {
+        return Err(format!("{} hook failed with {}", hook.name(), status));
+    }
+
+    Ok(())
+}
+
+/// Runs a hook and reports its failure in the service's terminal instead of
+/// returning it, for hooks that can't abort anything.
+pub fn run_hook_reporting(service: &ServiceConfig, hook: Hook) {
+    if let Err(e) = run_hook(service, hook) {
+        pty_manager::emit_banner(&service.id, &e);
+    }
+}
+
+/// Runs a service's post_start hook once the service is ready, listening on
+/// every port it declares. Services without ports, and tasks, are ready as
+/// soon as they're spawned. The hook is skipped when the service stops or
+/// isn't ready in time.
+pub fn run_post_start(service: &ServiceConfig) {
+    let deadline = Instant::now() + READY_TIMEOUT;
+    while service.kind == ServiceKind::Service && !service.ports.is_empty() {
+        if ports::is_ready(service, &Snapshot::take(), &endpoints::listening_sockets()) {
+            break;
+        }
+        if !matches!(
+            pty_manager::run_state(&service.id),
+            RunState::Running | RunState::Paused
+        ) {
+            return;
+        }
+        if Instant::now() > deadline {
+            pty_manager::emit_banner(
+                &service.id,
+                &format!(
+                    "not listening on its ports after {}s, skipping post_start",
+                    READY_TIMEOUT.as_secs()
+                ),
+            );
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+    run_hook_reporting(service, Hook::PostStart);
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::config::{Config, RawConfig};
+
+    fn service(pre_start: &str) -> ServiceConfig {
+        let raw: RawConfig = serde_yaml::from_str(&format!(
+            "name: Test\nservices:\n  - prog: sleep\n    pre_start: {pre_start}\n"
+        ))
+        .unwrap();
+        let mut service = Config::try_from(raw).unwrap().services.remove(0);
+        service.wdir = std::env::temp_dir().to_string_lossy().into_owned();
+        service
+    }
+
+    #[test]
+    fn fails_on_a_non_zero_exit() {
+        assert!(run_hook(&service("exit 3"), Hook::PreStart).is_err());
+        assert!(run_hook(&service("'true'"), Hook::PreStart).is_ok());
+        assert!(run_hook(&service("'true'"), Hook::PostStop).is_ok());
+    }
+
+    #[test]
+    fn returns_while_the_hook_left_something_running() {
+        let started = Instant::now();
+        assert!(run_hook(&service("sleep 10 &"), Hook::PreStart).is_ok());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
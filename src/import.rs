@@ -0,0 +1,448 @@
+use clap::ValueEnum;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::{RawConfig, RawServiceConfig};
+
+// The `PORT` Procfile processes get, like foreman: 5000, 5100, 5200...
+const PROCFILE_BASE_PORT: u16 = 5000;
+const PROCFILE_PORT_STEP: u16 = 100;
+
+// Commands using any of these need a shell to run as written
+const SHELL_CHARS: &[char] = &['$', '&', '|', ';', '<', '>', '(', ')', '`', '*', '?', '~'];
+
+/// Where `runz init` can import services from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Source {
+    Procfile,
+    #[value(name = "package.json", alias = "package", alias = "npm")]
+    PackageJson,
+    #[value(alias = "docker-compose")]
+    Compose,
+}
+
+impl Source {
+    fn default_files(&self) -> &'static [&'static str] {
+        match self {
+            Source::Procfile => &["Procfile"],
+            Source::PackageJson => &["package.json"],
+            Source::Compose => &[
+                "compose.yaml",
+                "compose.yml",
+                "docker-compose.yaml",
+                "docker-compose.yml",
+            ],
+        }
+    }
+}
+
+pub fn is_procfile(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name == "Procfile" || name.starts_with("Procfile."))
+}
+
+// Names the workspace after the directory the imported file lives in
+fn workspace_name(path: &Path) -> String {
+    path.parent()
+        .and_then(|dir| std::fs::canonicalize(dir).ok())
+        .and_then(|dir| dir.file_name().map(|name| name.to_string_lossy().to_string()))
+        .unwrap_or("Workspace".to_string())
+}
+
+// Splits a command line into program and arguments, going through a shell
+// when the command relies on shell features
+fn command(line: &str) -> anyhow::Result<(String, Vec<String>)> {
+    if line.contains(SHELL_CHARS) {
+        return Ok(("sh".to_string(), vec!["-c".to_string(), line.to_string()]));
+    }
+
+    let mut words = shell_words::split(line)?.into_iter();
+    let Some(prog) = words.next() else {
+        anyhow::bail!("Empty command");
+    };
+    Ok((prog, words.collect()))
+}
+
+/// Reads a Procfile: one `name: command` service per line. Each service gets
+/// its own `PORT`, as Procfile commands commonly listen on `$PORT`.
+pub fn from_procfile(data: &str, path: &Path) -> anyhow::Result<RawConfig> {
+    let mut services = vec![];
+
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((name, line)) = line.split_once(':') else {
+            anyhow::bail!("Invalid Procfile line {}, expected <name>: <command>", number + 1);
+        };
+        let (prog, args) = command(line.trim())
+            .map_err(|err| anyhow::anyhow!("Invalid Procfile line {}: {}", number + 1, err))?;
+
+        let port = u16::try_from(services.len())
+            .ok()
+            .and_then(|index| index.checked_mul(PROCFILE_PORT_STEP))
+            .and_then(|offset| offset.checked_add(PROCFILE_BASE_PORT))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Too many Procfile processes at line {}, no port left to give",
+                    number + 1
+                )
+            })?;
+        services.push(RawServiceConfig {
+            name: Some(name.trim().to_string()),
+            prog,
+            args: Some(args),
+            env: Some(BTreeMap::from([("PORT".to_string(), port.to_string())])),
+            ..Default::default()
+        });
+    }
+
+    Ok(RawConfig {
+        name: workspace_name(path),
+        services,
+        ..Default::default()
+    })
+}
+
+// The package manager a project uses, judging by its lockfile
+fn package_manager(dir: &Path) -> &'static str {
+    if dir.join("bun.lock").exists() || dir.join("bun.lockb").exists() {
+        "bun"
+    } else if dir.join("pnpm-lock.yaml").exists() {
+        "pnpm"
+    } else if dir.join("yarn.lock").exists() {
+        "yarn"
+    } else {
+        "npm"
+    }
+}
+
+// Expands npm workspace patterns, supporting a trailing `/*`
+fn workspace_dirs(root: &Path, patterns: &[Value]) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    for pattern in patterns.iter().filter_map(Value::as_str) {
+        match pattern.strip_suffix("/*") {
+            Some(parent) => {
+                let Ok(entries) = std::fs::read_dir(root.join(parent)) else {
+                    continue;
+                };
+                let mut found: Vec<PathBuf> = entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.join("package.json").exists())
+                    .collect();
+                found.sort();
+                dirs.extend(found);
+            }
+            None => dirs.push(root.join(pattern)),
+        }
+    }
+    dirs
+}
+
+/// Reads a package.json. With npm workspaces, every workspace package with a
+/// `dev` or `start` script becomes a service. Otherwise every script does,
+/// only `dev` and `start` being started automatically.
+pub fn from_package_json(data: &str, path: &Path) -> anyhow::Result<RawConfig> {
+    let package: Value = serde_json::from_str(data)?;
+    let root = path.parent().unwrap_or(Path::new("."));
+    let manager = package_manager(root);
+    let mut services = vec![];
+
+    let workspaces = match package.get("workspaces") {
+        Some(Value::Array(patterns)) => Some(patterns.clone()),
+        Some(Value::Object(map)) => map
+            .get("packages")
+            .and_then(Value::as_array)
+            .cloned(),
+        _ => None,
+    };
+
+    if let Some(patterns) = workspaces {
+        for dir in workspace_dirs(root, &patterns) {
+            let Ok(data) = std::fs::read_to_string(dir.join("package.json")) else {
+                continue;
+            };
+            let member: Value = serde_json::from_str(&data)?;
+            let scripts = member.get("scripts");
+            let Some(script) = ["dev", "start"]
+                .into_iter()
+                .find(|script| scripts.and_then(|s| s.get(script)).is_some())
+            else {
+                continue;
+            };
+
+            let relative = dir.strip_prefix(root).unwrap_or(&dir);
+            services.push(RawServiceConfig {
+                name: member
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .map(str::to_string)
+                    .or_else(|| Some(relative.display().to_string())),
+                wdir: Some(format!("./{}", relative.display())),
+                prog: manager.to_string(),
+                args: Some(vec!["run".to_string(), script.to_string()]),
+                ..Default::default()
+            });
+        }
+    } else if let Some(scripts) = package.get("scripts").and_then(Value::as_object) {
+        for script in scripts.keys() {
+            services.push(RawServiceConfig {
+                name: Some(script.clone()),
+                prog: manager.to_string(),
+                args: Some(vec!["run".to_string(), script.clone()]),
+                autostart: (script != "dev" && script != "start").then_some(false),
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(RawConfig {
+        name: package
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| workspace_name(path)),
+        services,
+        ..Default::default()
+    })
+}
+
+/// Reads a compose file, running each of its services through `docker compose up`.
+/// Services keep the order they're declared in.
+pub fn from_compose(data: &str, path: &Path) -> anyhow::Result<RawConfig> {
+    let compose: serde_yaml::Value = serde_yaml::from_str(data)?;
+    let file = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let Some(entries) = compose
+        .get("services")
+        .and_then(serde_yaml::Value::as_mapping)
+    else {
+        anyhow::bail!("No services defined in {}", path.display());
+    };
+
+    let names = |value: &serde_yaml::Value| -> Vec<String> {
+        match value {
+            serde_yaml::Value::Sequence(names) => names
+                .iter()
+                .filter_map(serde_yaml::Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            serde_yaml::Value::Mapping(map) => map
+                .keys()
+                .filter_map(serde_yaml::Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            _ => vec![],
+        }
+    };
+
+    let mut services = vec![];
+    for (name, service) in entries {
+        let Some(name) = name.as_str() else {
+            anyhow::bail!("Invalid service name {:?} in {}", name, path.display());
+        };
+        let depends_on = service.get("depends_on").map(names).unwrap_or_default();
+
+        services.push(RawServiceConfig {
+            name: Some(name.to_string()),
+            prog: "docker".to_string(),
+            args: Some(
+                ["compose", "-f", &file, "up", name]
+                    .map(str::to_string)
+                    .to_vec(),
+            ),
+            depends_on: (!depends_on.is_empty()).then_some(depends_on),
+            ..Default::default()
+        });
+    }
+
+    Ok(RawConfig {
+        name: compose
+            .get("name")
+            .and_then(serde_yaml::Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| workspace_name(path)),
+        services,
+        ..Default::default()
+    })
+}
+
+// Drops unset fields, so the generated file only has what was imported
+fn strip_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_nulls),
+        _ => {}
+    }
+}
+
+/// Generates a runz.yml from an existing Procfile, package.json or compose
+/// file. It's written next to that file, as the paths it holds (compose
+/// files, workspace directories) are relative to it.
+pub fn init(source: Source, file: Option<PathBuf>, force: bool) -> anyhow::Result<PathBuf> {
+    let cwd = std::env::current_dir()?;
+    let path = match file {
+        Some(file) => cwd.join(file),
+        None => source
+            .default_files()
+            .iter()
+            .map(|name| cwd.join(name))
+            .find(|path| path.exists())
+            .ok_or_else(|| anyhow::anyhow!("No {} found in {}", source.default_files()[0], cwd.display()))?,
+    };
+
+    let output = path.parent().unwrap_or(&cwd).join("runz.yml");
+    if output.exists() && !force {
+        anyhow::bail!("{} already exists, pass --force to overwrite it", output.display());
+    }
+
+    let data = std::fs::read_to_string(&path)
+        .map_err(|err| anyhow::anyhow!("Failed to read {}: {}", path.display(), err))?;
+    let raw_config = match source {
+        Source::Procfile => from_procfile(&data, &path)?,
+        Source::PackageJson => from_package_json(&data, &path)?,
+        Source::Compose => from_compose(&data, &path)?,
+    };
+
+    if raw_config.services.is_empty() {
+        anyhow::bail!("No services found in {}", path.display());
+    }
+
+    let mut value = serde_json::to_value(&raw_config)?;
+    strip_nulls(&mut value);
+    std::fs::write(&output, serde_yaml::to_string(&value)?)?;
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Nothing exists there, so the names and package manager are the defaults
+    fn path(file: &str) -> PathBuf {
+        Path::new("/nonexistent/runz-import").join(file)
+    }
+
+    #[test]
+    fn reads_procfiles() {
+        let data =
+            "# processes\nweb: bundle exec puma -p $PORT\n\nworker:  sidekiq -q 'low prio'\n";
+        let config = from_procfile(data, &path("Procfile")).unwrap();
+
+        assert_eq!(config.name, "Workspace");
+        let [web, worker] = &config.services[..] else {
+            panic!("expected two services, got {:?}", config.services);
+        };
+        assert_eq!(web.name.as_deref(), Some("web"));
+        assert_eq!(web.prog, "sh");
+        assert_eq!(
+            web.args.as_deref(),
+            Some(&["-c".to_string(), "bundle exec puma -p $PORT".to_string()][..])
+        );
+        assert_eq!(web.env.as_ref().unwrap()["PORT"], "5000");
+        assert_eq!(worker.prog, "sidekiq");
+        assert_eq!(
+            worker.args.as_deref(),
+            Some(&["-q".to_string(), "low prio".to_string()][..])
+        );
+        assert_eq!(worker.env.as_ref().unwrap()["PORT"], "5100");
+
+        let err = from_procfile("web bundle exec puma\n", &path("Procfile")).unwrap_err();
+        assert!(err.to_string().contains("line 1"), "{err}");
+    }
+
+    #[test]
+    fn runs_out_of_procfile_ports() {
+        let data: String = (0..700).map(|i| format!("p{i}: sleep 1\n")).collect();
+        let err = from_procfile(&data, &path("Procfile")).unwrap_err();
+        // 5000 + 605 * 100 is the last port that fits
+        assert!(err.to_string().contains("line 607"), "{err}");
+    }
+
+    #[test]
+    fn reads_package_json_scripts() {
+        let data = r#"{"name": "shop", "scripts": {"build": "vite build", "dev": "vite"}}"#;
+        let config = from_package_json(data, &path("package.json")).unwrap();
+
+        assert_eq!(config.name, "shop");
+        let [build, dev] = &config.services[..] else {
+            panic!("expected two services, got {:?}", config.services);
+        };
+        assert_eq!(build.prog, "npm");
+        assert_eq!(
+            build.args.as_deref(),
+            Some(&["run".to_string(), "build".to_string()][..])
+        );
+        assert_eq!(build.autostart, Some(false));
+        assert_eq!(dev.autostart, None);
+    }
+
+    #[test]
+    fn reads_compose_files() {
+        let data = "
+services:
+  db:
+    image: postgres
+  api:
+    build: .
+    depends_on: [db]
+  web:
+    depends_on:
+      api:
+        condition: service_started
+";
+        let config = from_compose(data, &path("compose.yaml")).unwrap();
+
+        let names: Vec<_> = config
+            .services
+            .iter()
+            .filter_map(|service| service.name.as_deref())
+            .collect();
+        assert_eq!(names, ["db", "api", "web"]);
+        let api = &config.services[1];
+        assert_eq!(api.prog, "docker");
+        assert_eq!(
+            api.args.as_deref(),
+            Some(&["compose", "-f", "compose.yaml", "up", "api"].map(str::to_string)[..])
+        );
+        assert_eq!(api.depends_on.as_deref(), Some(&["db".to_string()][..]));
+        assert_eq!(config.services[0].depends_on, None);
+        assert_eq!(
+            config.services[2].depends_on.as_deref(),
+            Some(&["api".to_string()][..])
+        );
+
+        assert!(from_compose("name: empty\n", &path("compose.yaml")).is_err());
+    }
+
+    #[test]
+    fn writes_the_config_next_to_the_imported_file() {
+        let dir = std::env::temp_dir().join(format!("runz-init-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let compose = dir.join("sub/docker-compose.yml");
+        std::fs::write(&compose, "services:\n  db:\n    image: postgres\n").unwrap();
+
+        let output = init(Source::Compose, Some(compose.clone()), false);
+        let written = output
+            .as_ref()
+            .ok()
+            .and_then(|output| std::fs::read_to_string(output).ok());
+        let again = init(Source::Compose, Some(compose), false);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(output.unwrap(), dir.join("sub/runz.yml"));
+        assert!(written.unwrap().contains("docker-compose.yml"));
+        assert!(again.unwrap_err().to_string().contains("already exists"));
+    }
+}
@@ -0,0 +1,252 @@
+use path_clean::PathClean;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::config::RawServiceConfig;
+
+// References nested deeper than this are assumed to be circular
+const MAX_DEPTH: usize = 16;
+
+/// Resolves `${...}` references in config values.
+///
+/// - `${VAR}` reads the process environment, then the workspace `vars`
+/// - `${VAR:-default}` falls back to `default` when `VAR` is unset or empty
+/// - `${workspace.root}` is the config file's directory, which services
+///   without a `wdir` run in, `${workspace.name}` the workspace name
+/// - `${services.<name>.<attr>}` reads another service's `name`, `prog`,
+///   `wdir`, `port` (its first one), `ports.<N>` (from 0) or `env.<VAR>`,
+///   where `<name>` is the service name, lowercased, with spaces as dashes
+/// - `$$` is a literal `$`
+pub struct Interpolator<'a> {
+    pub root: &'a Path,
+    pub name: &'a str,
+    pub vars: &'a BTreeMap<String, String>,
+    pub services: &'a [RawServiceConfig],
+}
+
+/// How a service is referred to from `${services.<key>...}`.
+pub fn service_key(name: &str) -> String {
+    name.trim().to_lowercase().replace([' ', '_'], "-")
+}
+
+impl Interpolator<'_> {
+    /// Interpolates a value, naming `field` in errors.
+    pub fn resolve(&self, input: &str, field: &str) -> anyhow::Result<String> {
+        self.resolve_at(input, 0)
+            .map_err(|err| anyhow::anyhow!("Invalid value for {}: {}", field, err))
+    }
+
+    fn resolve_at(&self, input: &str, depth: usize) -> anyhow::Result<String> {
+        if depth > MAX_DEPTH {
+            anyhow::bail!("circular reference");
+        }
+
+        let mut output = String::with_capacity(input.len());
+        let mut rest = input;
+
+        while let Some(start) = rest.find('$') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            if let Some(after) = rest.strip_prefix("$$") {
+                output.push('$');
+                rest = after;
+                continue;
+            }
+
+            let Some(body) = rest.strip_prefix("${") else {
+                output.push('$');
+                rest = &rest[1..];
+                continue;
+            };
+
+            let end = matching_brace(body)
+                .ok_or_else(|| anyhow::anyhow!("unclosed '${{' in '{}'", input))?;
+            output.push_str(&self.expand(&body[..end], depth)?);
+            rest = &body[end + 1..];
+        }
+
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    fn expand(&self, expression: &str, depth: usize) -> anyhow::Result<String> {
+        let (name, default) = match expression.split_once(":-") {
+            Some((name, default)) => (name.trim(), Some(default)),
+            None => (expression.trim(), None),
+        };
+
+        match self.lookup(name, depth)? {
+            Some(value) if !(value.is_empty() && default.is_some()) => Ok(value),
+            _ => match default {
+                Some(default) => self.resolve_at(default, depth + 1),
+                None => anyhow::bail!("undefined variable '{}'", name),
+            },
+        }
+    }
+
+    fn lookup(&self, name: &str, depth: usize) -> anyhow::Result<Option<String>> {
+        if let Some(attribute) = name.strip_prefix("workspace.") {
+            return Ok(match attribute {
+                "root" => Some(self.root.display().to_string()),
+                "name" => Some(self.name.to_string()),
+                _ => None,
+            });
+        }
+
+        if let Some(reference) = name.strip_prefix("services.") {
+            let Some((key, attribute)) = reference.split_once('.') else {
+                return Ok(None);
+            };
+            return self.lookup_service(key, attribute, depth);
+        }
+
+        if let Some(value) = std::env::var_os(name) {
+            return Ok(Some(value.to_string_lossy().to_string()));
+        }
+
+        match self.vars.get(name) {
+            Some(value) => self.resolve_at(value, depth + 1).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn lookup_service(
+        &self,
+        key: &str,
+        attribute: &str,
+        depth: usize,
+    ) -> anyhow::Result<Option<String>> {
+        let Some(service) = self.services.iter().find(|s| {
+            service_key(s.name.as_deref().unwrap_or(&s.prog)) == service_key(key)
+        }) else {
+            anyhow::bail!("unknown service '{}'", key);
+        };
+
+        let raw = match attribute {
+            "name" => Some(service.name.clone().unwrap_or(service.prog.clone())),
+            "prog" => Some(service.prog.clone()),
+            "wdir" => {
+                let wdir = service.wdir.as_deref().unwrap_or(".");
+                let wdir = self.resolve_at(wdir, depth + 1)?;
+                return Ok(Some(self.root.join(wdir).clean().display().to_string()));
+            }
+            "port" => return Ok(port(service, 0)),
+            _ => {
+                if let Some(var) = attribute.strip_prefix("env.") {
+                    service.env.as_ref().and_then(|env| env.get(var)).cloned()
+                } else if let Some(index) = attribute
+                    .strip_prefix("ports.")
+                    .and_then(|index| index.parse().ok())
+                {
+                    return Ok(port(service, index));
+                } else {
+                    anyhow::bail!(
+                        "unknown attribute '{}' of service '{}', expected name, prog, wdir, port, ports.<N> or env.<VAR>",
+                        attribute,
+                        key
+                    );
+                }
+            }
+        };
+
+        match raw {
+            Some(value) => self.resolve_at(&value, depth + 1).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+// The service's port at `index`, if it declares that many
+fn port(service: &RawServiceConfig, index: usize) -> Option<String> {
+    let ports = service.ports.as_ref()?;
+    ports.get(index).map(|port| port.to_string())
+}
+
+// Index of the `}` closing an expression, skipping nested `${...}` in defaults
+fn matching_brace(body: &str) -> Option<usize> {
+    let mut depth = 0;
+    let bytes = body.as_bytes();
+
+    for (index, byte) in bytes.iter().enumerate() {
+        match byte {
+            b'{' if index > 0 && bytes[index - 1] == b'$' => depth += 1,
+            b'}' if depth == 0 => return Some(index),
+            b'}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(name: &str) -> RawServiceConfig {
+        RawServiceConfig {
+            name: Some(name.to_string()),
+            prog: "npm".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn resolve(input: &str) -> anyhow::Result<String> {
+        let vars = BTreeMap::from([
+            ("HOST".to_string(), "localhost".to_string()),
+            ("URL".to_string(), "http://${HOST}".to_string()),
+            ("LOOP".to_string(), "${LOOP}".to_string()),
+        ]);
+        let services = vec![
+            RawServiceConfig {
+                wdir: Some("apps/api".to_string()),
+                env: Some(BTreeMap::from([("PORT".to_string(), "8080".to_string())])),
+                ports: Some(vec![8080, 9090]),
+                ..service("API Server")
+            },
+            service("web"),
+        ];
+        let interpolator = Interpolator {
+            root: Path::new("/work"),
+            name: "Shop",
+            vars: &vars,
+            services: &services,
+        };
+        interpolator.resolve(input, "'web' env.X")
+    }
+
+    #[test]
+    fn resolves_vars_and_defaults() {
+        assert_eq!(resolve("${HOST}:3000").unwrap(), "localhost:3000");
+        assert_eq!(resolve("${URL}/api").unwrap(), "http://localhost/api");
+        assert_eq!(resolve("${RUNZ_UNSET_VAR:-${HOST}}").unwrap(), "localhost");
+        assert_eq!(resolve("$$HOME costs $5").unwrap(), "$HOME costs $5");
+    }
+
+    #[test]
+    fn resolves_workspace_attributes() {
+        assert_eq!(resolve("${workspace.root}").unwrap(), "/work");
+        assert_eq!(resolve("${workspace.name}").unwrap(), "Shop");
+    }
+
+    #[test]
+    fn resolves_service_attributes() {
+        assert_eq!(resolve("${services.api-server.name}").unwrap(), "API Server");
+        assert_eq!(resolve("${services.api-server.wdir}").unwrap(), "/work/apps/api");
+        assert_eq!(resolve("${services.api-server.env.PORT}").unwrap(), "8080");
+        assert_eq!(resolve("${services.api-server.port}").unwrap(), "8080");
+        assert_eq!(resolve("${services.api-server.ports.1}").unwrap(), "9090");
+        assert_eq!(resolve("${services.web.port:-3000}").unwrap(), "3000");
+    }
+
+    #[test]
+    fn reports_what_is_wrong() {
+        let error = |input| resolve(input).unwrap_err().to_string();
+        assert!(error("${RUNZ_UNSET_VAR}").contains("undefined variable 'RUNZ_UNSET_VAR'"));
+        assert!(error("${services.db.name}").contains("unknown service 'db'"));
+        assert!(error("${services.web.host}").contains("unknown attribute 'host'"));
+        assert!(error("${LOOP}").contains("circular reference"));
+        assert!(error("${HOST").contains("unclosed"));
+        assert!(error("${HOST").starts_with("Invalid value for 'web' env.X"));
+    }
+}
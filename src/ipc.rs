@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{cmd, emitter};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpcMsgData {
+    pub name: String,
+    pub payload: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpcMsg {
+    pub id: String,
+    pub event: IpcMsgData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResizePayload {
+    pub rows: u16,
+    pub cols: u16,
+}
+
+/// Runs a command the frontend sent, from the webview or a remote browser.
+/// Failures are reported to the terminal the command was meant for.
+pub fn handle(msg: IpcMsg) {
+    let id = msg.id.clone();
+    if let Err(e) = run(msg) {
+        emitter::emit(id, json!({ "type": "error", "data": e }));
+    }
+}
+
+fn run(msg: IpcMsg) -> Result<(), String> {
+    let id = msg.id;
+    let payload = msg.event.payload;
+    let text = payload
+        .as_ref()
+        .and_then(|p| p.as_str())
+        .map(str::to_string);
+
+    match msg.event.name.as_str() {
+        "open_link" => payload.map_or(Ok(()), |p| cmd::open_link(p.to_string())),
+        "start_terminal" => cmd::start_terminal(id),
+        "resolve_port_conflict" => {
+            text.map_or(Ok(()), |choice| cmd::resolve_port_conflict(id, choice))
+        }
+        "stop_terminal" => cmd::stop_terminal(id),
+        "restart_terminal" => cmd::restart_terminal(id),
+        "signal_terminal" => text.map_or(Ok(()), |signal| cmd::signal_terminal(id, signal)),
+        "scale_terminal" => match payload.as_ref().and_then(|p| p.as_u64()) {
+            Some(replicas) => cmd::scale_terminal(id, u32::try_from(replicas).unwrap_or(u32::MAX)),
+            None => Ok(()),
+        },
+        "pause_terminal" => cmd::pause_terminal(id),
+        "resume_terminal" => cmd::resume_terminal(id),
+        "start_all_terminals" => cmd::start_all_terminals(),
+        "start_group_terminals" => text.map_or(Ok(()), cmd::start_group_terminals),
+        "stop_all_terminals" => cmd::stop_all_terminals(),
+        "restart_all_terminals" => cmd::restart_all_terminals(),
+        "input_terminal" => payload.map_or(Ok(()), |p| cmd::input_terminal(id, p.to_string())),
+        "resize_terminal" => {
+            match payload.and_then(|p| serde_json::from_value::<ResizePayload>(p).ok()) {
+                Some(size) => cmd::resize_terminal(id, size.cols, size.rows),
+                None => Ok(()),
+            }
+        }
+        "close_window" => {
+            emitter::emit("close_window".to_string(), json!({}));
+            Ok(())
+        }
+        "minimize_window" => {
+            emitter::emit("minimize_window".to_string(), json!({}));
+            Ok(())
+        }
+        "maximize_window" => {
+            emitter::emit("maximize_window".to_string(), json!({}));
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
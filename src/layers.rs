@@ -0,0 +1,490 @@
+use path_clean::PathClean;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::parse_value;
+use crate::import;
+
+/// A config assembled from several files: the `extends` base, the `include`d
+/// files, the file itself and its local override, merged in that order.
+/// Included files only contribute their services and vars.
+///
+/// Objects are merged key by key, services are merged by name, and any other
+/// value replaces the previous one. Lists are replaced, unless the key ends
+/// with `+` (e.g. `args+`), in which case they are appended.
+pub struct Layered {
+    pub value: Value,
+    // Directory of the top-level config file, None for inline configs
+    pub root: Option<PathBuf>,
+    // Which file set each value, keyed by path (e.g. `services.1.args`)
+    origins: BTreeMap<String, PathBuf>,
+}
+
+impl Layered {
+    fn empty() -> Self {
+        Self {
+            value: Value::Object(Map::new()),
+            root: None,
+            origins: BTreeMap::new(),
+        }
+    }
+
+    /// The file that set the value at `path`, or the closest parent value.
+    pub fn origin(&self, path: &str) -> Option<&Path> {
+        let mut path = path;
+        loop {
+            if let Some(origin) = self.origins.get(path) {
+                return Some(origin);
+            }
+            match path.rfind('.') {
+                Some(index) => path = &path[..index],
+                None => return self.origins.get("").map(PathBuf::as_path),
+            }
+        }
+    }
+
+    /// Deserializes the merged value, naming the file a bad value came from.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> anyhow::Result<T> {
+        serde_path_to_error::deserialize(&self.value).map_err(|err| {
+            let path = err.path().to_string().replace('[', ".").replace(']', "");
+            match self.origin(&path) {
+                Some(origin) => anyhow::anyhow!("{} (in {})", err, origin.display()),
+                None => anyhow::anyhow!("{}", err),
+            }
+        })
+    }
+}
+
+// Services are identified by their name, falling back to the program
+fn service_key(service: &Value) -> Option<&str> {
+    service
+        .get("name")
+        .or_else(|| service.get("prog"))
+        .and_then(Value::as_str)
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn set_value(
+    target: &mut Value,
+    value: Value,
+    path: &str,
+    origin: &Path,
+    origins: &mut BTreeMap<String, PathBuf>,
+) {
+    let prefix = format!("{path}.");
+    origins.retain(|key, _| key != path && !key.starts_with(&prefix));
+    origins.insert(path.to_string(), origin.to_path_buf());
+    *target = value;
+}
+
+fn merge_value(
+    target: &mut Value,
+    value: Value,
+    path: &str,
+    origin: &Path,
+    origins: &mut BTreeMap<String, PathBuf>,
+) {
+    let Value::Object(map) = value else {
+        return set_value(target, value, path, origin, origins);
+    };
+    if !target.is_object() {
+        set_value(target, Value::Object(Map::new()), path, origin, origins);
+    }
+    let Value::Object(target_map) = target else {
+        unreachable!()
+    };
+
+    for (key, value) in map {
+        // `key+` appends to the list instead of replacing it
+        if let Some(key) = key.strip_suffix('+') {
+            let key_path = child_path(path, key);
+            match (target_map.get_mut(key), value) {
+                (Some(Value::Array(items)), Value::Array(extra)) => {
+                    for item in extra {
+                        let item_path = child_path(&key_path, &items.len().to_string());
+                        origins.insert(item_path, origin.to_path_buf());
+                        items.push(item);
+                    }
+                }
+                (_, value) => {
+                    let entry = target_map.entry(key).or_insert(Value::Null);
+                    set_value(entry, value, &key_path, origin, origins);
+                }
+            }
+            continue;
+        }
+
+        let key_path = child_path(path, &key);
+        let entry = target_map.entry(key.clone()).or_insert(Value::Null);
+        if path.is_empty() && key == "services" {
+            merge_services(entry, value, &key_path, origin, origins);
+        } else {
+            merge_value(entry, value, &key_path, origin, origins);
+        }
+    }
+}
+
+fn merge_services(
+    target: &mut Value,
+    value: Value,
+    path: &str,
+    origin: &Path,
+    origins: &mut BTreeMap<String, PathBuf>,
+) {
+    let Value::Array(overrides) = value else {
+        return set_value(target, value, path, origin, origins);
+    };
+    if !target.is_array() {
+        set_value(target, Value::Array(vec![]), path, origin, origins);
+    }
+    let Value::Array(services) = target else {
+        unreachable!()
+    };
+
+    for service in overrides {
+        let index = service_key(&service)
+            .and_then(|key| services.iter().position(|s| service_key(s) == Some(key)))
+            .unwrap_or(services.len());
+
+        if index == services.len() {
+            services.push(Value::Null);
+        }
+
+        let service_path = child_path(path, &index.to_string());
+        merge_value(
+            &mut services[index],
+            service,
+            &service_path,
+            origin,
+            origins,
+        );
+    }
+}
+
+fn take_string_list(value: &mut Value, key: &str) -> anyhow::Result<Vec<String>> {
+    let Some(entry) = value.as_object_mut().and_then(|map| map.remove(key)) else {
+        return Ok(vec![]);
+    };
+    match entry {
+        Value::Null => Ok(vec![]),
+        Value::String(item) => Ok(vec![item]),
+        Value::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Value::String(item) => Ok(item),
+                _ => anyhow::bail!("'{}' must be a list of file paths", key),
+            })
+            .collect(),
+        _ => anyhow::bail!("'{}' must be a list of file paths", key),
+    }
+}
+
+fn config_type_of(path: &Path) -> &str {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("toml")
+}
+
+// Makes relative `wdir` values relative to `basedir`, the directory of the
+// file setting them, as the merged config no longer tells which file that
+// was. Values starting with a variable are left for interpolation.
+fn rebase_wdirs(value: &mut Value, basedir: &Path) {
+    for key in ["services", "services+"] {
+        let Some(Value::Array(services)) = value.get_mut(key) else {
+            continue;
+        };
+        for service in services {
+            if let Some(Value::String(wdir)) = service.get_mut("wdir") {
+                if !wdir.starts_with('$') && Path::new(wdir.as_str()).is_relative() {
+                    *wdir = basedir.join(&*wdir).clean().display().to_string();
+                }
+            }
+        }
+    }
+}
+
+// What an included file adds to the config that includes it
+const INCLUDED_KEYS: [&str; 2] = ["services", "vars"];
+
+/// Merges a parsed document into `layered`, after what it extends and
+/// includes. Paths, including those of services' `wdir`, are relative to
+/// `basedir`. With `only`, the document's other top-level keys are left out,
+/// along with those of its own bases.
+fn merge_document(
+    layered: &mut Layered,
+    mut value: Value,
+    source: &Path,
+    basedir: &Path,
+    stack: &mut Vec<PathBuf>,
+    only: Option<&[&str]>,
+) -> anyhow::Result<()> {
+    let annotate = |err: anyhow::Error| anyhow::anyhow!("{} (in {})", err, source.display());
+
+    let extends = take_string_list(&mut value, "extends").map_err(annotate)?;
+    let includes = take_string_list(&mut value, "include").map_err(annotate)?;
+
+    for file in &extends {
+        merge_file(layered, &basedir.join(file).clean(), stack, only)?;
+    }
+    for file in &includes {
+        merge_file(
+            layered,
+            &basedir.join(file).clean(),
+            stack,
+            Some(&INCLUDED_KEYS),
+        )?;
+    }
+
+    if let (Some(keys), Some(map)) = (only, value.as_object_mut()) {
+        map.retain(|key, _| keys.contains(&key.trim_end_matches('+')));
+    }
+    rebase_wdirs(&mut value, basedir);
+
+    layered
+        .origins
+        .entry(String::new())
+        .or_insert(source.to_path_buf());
+    merge_value(&mut layered.value, value, "", source, &mut layered.origins);
+
+    Ok(())
+}
+
+fn merge_file(
+    layered: &mut Layered,
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    only: Option<&[&str]>,
+) -> anyhow::Result<()> {
+    if stack.iter().any(|p| p == path) {
+        anyhow::bail!("Config file {} includes itself", path.display());
+    }
+
+    let data = std::fs::read_to_string(path)
+        .map_err(|err| anyhow::anyhow!("Failed to read {}: {}", path.display(), err))?;
+    let value = if import::is_procfile(path) {
+        import::from_procfile(&data, path).and_then(|raw| Ok(serde_json::to_value(raw)?))
+    } else {
+        parse_value(config_type_of(path), &data)
+    }
+    .map_err(|err| anyhow::anyhow!("{} (in {})", err, path.display()))?;
+
+    stack.push(path.to_path_buf());
+    let basedir = path.parent().unwrap_or(Path::new("."));
+    let result = merge_document(layered, value, path, basedir, stack, only);
+    stack.pop();
+
+    result
+}
+
+/// Looks for `<name>.local.<ext>` next to `<name>.<ext>`, in any supported format.
+fn find_local_override(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+    let dir = path.parent()?;
+
+    ["yaml", "yml", "json", "jsonc", "toml"]
+        .iter()
+        .map(|ext| dir.join(format!("{stem}.local.{ext}")))
+        .find(|candidate| candidate.exists())
+}
+
+/// Loads a config file with everything it extends and includes, plus its
+/// local override if there is one.
+pub fn load_file(path: &Path) -> anyhow::Result<Layered> {
+    let path = std::env::current_dir()?.join(path).clean();
+    let mut layered = Layered::empty();
+    layered.root = path.parent().map(Path::to_path_buf);
+    layered.origins.insert(String::new(), path.clone());
+    merge_file(&mut layered, &path, &mut vec![], None)?;
+
+    if let Some(local) = find_local_override(&path) {
+        eprintln!("Using local override: {}", local.display());
+        merge_file(&mut layered, &local, &mut vec![path], None)?;
+    }
+
+    Ok(layered)
+}
+
+/// Loads an inline config, resolving what it includes against the current directory.
+pub fn load_str(config_type: &str, config_data: &str) -> anyhow::Result<Layered> {
+    let source = PathBuf::from("<command line>");
+    let value = parse_value(config_type, config_data)?;
+
+    let mut layered = Layered::empty();
+    layered.origins.insert(String::new(), source.clone());
+    merge_document(
+        &mut layered,
+        value,
+        &source,
+        &std::env::current_dir()?,
+        &mut vec![],
+        None,
+    )?;
+
+    Ok(layered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Merges each (file, document) in order, as the files of a config would be
+    fn merge(documents: Vec<(&str, Value)>) -> Layered {
+        let mut layered = Layered::empty();
+        for (file, value) in documents {
+            merge_value(
+                &mut layered.value,
+                value,
+                "",
+                Path::new(file),
+                &mut layered.origins,
+            );
+        }
+        layered
+    }
+
+    #[test]
+    fn merges_objects_and_replaces_the_rest() {
+        let layered = merge(vec![
+            (
+                "base.yml",
+                json!({"name": "Base", "vars": {"A": "1", "B": "2"}, "tags": ["x"]}),
+            ),
+            (
+                "runz.yml",
+                json!({"name": "Main", "vars": {"B": "3"}, "tags": ["y"]}),
+            ),
+        ]);
+
+        assert_eq!(
+            layered.value,
+            json!({"name": "Main", "vars": {"A": "1", "B": "3"}, "tags": ["y"]})
+        );
+        assert_eq!(layered.origin("vars.A"), Some(Path::new("base.yml")));
+        assert_eq!(layered.origin("vars.B"), Some(Path::new("runz.yml")));
+        assert_eq!(layered.origin("tags.0"), Some(Path::new("runz.yml")));
+    }
+
+    #[test]
+    fn appends_with_plus_keys() {
+        let layered = merge(vec![
+            (
+                "base.yml",
+                json!({"services": [{"name": "api", "args": ["run"]}]}),
+            ),
+            (
+                "runz.local.yml",
+                json!({"services": [{"name": "api", "args+": ["-race"], "ports+": [8080]}]}),
+            ),
+        ]);
+
+        let api = &layered.value["services"][0];
+        assert_eq!(api["args"], json!(["run", "-race"]));
+        // Nothing to append to, the list is set as is
+        assert_eq!(api["ports"], json!([8080]));
+        assert_eq!(
+            layered.origin("services.0.args.0"),
+            Some(Path::new("base.yml"))
+        );
+        assert_eq!(
+            layered.origin("services.0.args.1"),
+            Some(Path::new("runz.local.yml"))
+        );
+    }
+
+    #[test]
+    fn merges_services_by_name() {
+        let layered = merge(vec![
+            (
+                "base.yml",
+                json!({"services": [
+                    {"name": "api", "prog": "go", "env": {"A": "1"}},
+                    {"prog": "redis-server"}
+                ]}),
+            ),
+            (
+                "runz.yml",
+                json!({"services": [
+                    {"prog": "redis-server", "args": ["--port", "6380"]},
+                    {"name": "web", "prog": "npm"},
+                    {"name": "api", "env": {"B": "2"}}
+                ]}),
+            ),
+        ]);
+
+        assert_eq!(
+            layered.value["services"],
+            json!([
+                {"name": "api", "prog": "go", "env": {"A": "1", "B": "2"}},
+                {"prog": "redis-server", "args": ["--port", "6380"]},
+                {"name": "web", "prog": "npm"}
+            ])
+        );
+    }
+
+    #[test]
+    fn included_documents_only_add_services_and_vars() {
+        let mut layered = Layered::empty();
+        let document = json!({
+            "name": "Extra",
+            "detach": false,
+            "vars": {"A": "1"},
+            "services+": [{"name": "worker", "prog": "celery"}]
+        });
+        merge_document(
+            &mut layered,
+            document,
+            Path::new("extra.yml"),
+            Path::new("/work"),
+            &mut vec![],
+            Some(&INCLUDED_KEYS),
+        )
+        .unwrap();
+
+        assert_eq!(
+            layered.value,
+            json!({"vars": {"A": "1"}, "services": [{"name": "worker", "prog": "celery"}]})
+        );
+    }
+
+    #[test]
+    fn resolves_paths_against_the_file_setting_them() {
+        let root = std::env::temp_dir().join(format!("runz-layers-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("backend/worker")).unwrap();
+        std::fs::write(
+            root.join("runz.yml"),
+            "name: Main\ninclude: backend/runz.yml\nservices:\n  - name: web\n    prog: npm\n    wdir: web\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("backend/runz.yml"),
+            "include: worker/runz.yml\nservices:\n  - name: api\n    prog: go\n    wdir: .\n  - name: db\n    prog: postgres\n    wdir: ${workspace.root}/db\n",
+        )
+        .unwrap();
+        std::fs::write(
+            root.join("backend/worker/runz.yml"),
+            "services:\n  - name: worker\n    prog: celery\n    wdir: ../../jobs\n",
+        )
+        .unwrap();
+
+        let layered = load_file(&root.join("runz.yml"));
+        let _ = std::fs::remove_dir_all(&root);
+        let services = &layered.unwrap().value["services"];
+
+        let wdir = |index: usize| services[index]["wdir"].as_str().unwrap().to_string();
+        assert_eq!(services[0]["name"], "worker");
+        assert_eq!(wdir(0), root.join("jobs").display().to_string());
+        assert_eq!(wdir(1), root.join("backend").display().to_string());
+        assert_eq!(wdir(2), "${workspace.root}/db");
+        assert_eq!(wdir(3), root.join("web").display().to_string());
+    }
+}
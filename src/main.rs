@@ -1,13 +1,8 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use muda::{Menu, PredefinedMenuItem, Submenu};
-use serde::{Deserialize, Serialize};
-use serde_json::json;
-use path_clean::PathClean;
-use slugify::slugify;
+use muda::{Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu};
 use std::sync::mpsc;
-use std::env;
 use tao::{
     dpi::LogicalSize,
     event::{Event, WindowEvent},
@@ -15,37 +10,36 @@ use tao::{
     window::{Icon, WindowBuilder},
 };
 use wry::WebViewBuilder;
+use cli::Command;
 use ctrlc;
 #[cfg(unix)]
 use signal_hook::{consts::SIGTERM, iterator::Signals};
-use tiny_http::{Header, Response, Server};
-use std::thread;
 
+mod assets;
+mod cli;
 mod cmd;
 mod config;
 mod emitter;
+mod endpoints;
+mod hooks;
+mod import;
+mod interpolate;
+mod ipc;
+mod layers;
+mod metrics;
+mod ports;
 mod pty_manager;
+mod remote;
+mod replicas;
+mod scheduler;
+mod server;
+mod stats;
+mod watcher;
+#[cfg(unix)]
+mod control;
 #[cfg(unix)]
 mod detach;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct IpcMsgData {
-    pub name: String,
-    pub payload: Option<serde_json::Value>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct IpcMsg {
-    pub id: String,
-    pub event: IpcMsgData,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ResizePayload {
-    pub rows: u16,
-    pub cols: u16,
-}
-
 fn load_icon() -> Icon {
     let icon_data = include_bytes!("../assets/icons/icon.png");
     let image = image::load_from_memory(icon_data)
@@ -56,7 +50,47 @@ fn load_icon() -> Icon {
     Icon::from_rgba(rgba, width, height).expect("Failed to create Icon")
 }
 
+// Ends a command line subcommand, reporting its error if it failed
+fn exit_with(result: anyhow::Result<()>) -> ! {
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    std::process::exit(0);
+}
+
 fn main() -> wry::Result<()> {
+    match &cli::args().command {
+        None | Some(Command::Run) => {}
+        Some(Command::Check) => exit_with(cli::check()),
+        Some(Command::Ctl { action }) => {
+            #[cfg(unix)]
+            exit_with(control::run(action));
+            #[cfg(not(unix))]
+            exit_with(Err(anyhow::anyhow!("runz ctl {:?} is only supported on Unix", action)));
+        }
+        Some(Command::Init { from, file, force }) => {
+            exit_with(import::init(*from, file.clone(), *force).map(|path| {
+                println!("Created {}", path.display());
+            }))
+        }
+        Some(Command::Schema) => {
+            println!("{}", config::schema());
+            return Ok(());
+        }
+        Some(Command::Completions { shell }) => {
+            cli::print_completions(*shell);
+            return Ok(());
+        }
+    }
+
+    // Debug builds load the UI from the Vite dev server, there's nothing to serve
+    if cfg!(debug_assertions) && cli::args().listen.is_some() {
+        exit_with(Err(anyhow::anyhow!(
+            "--listen is only available in release builds, debug builds load the UI from the Vite dev server"
+        )));
+    }
+
     // --- register Ctrl-C on all platforms ---
     ctrlc::set_handler(move || {
         crate::pty_manager::cleanup_all();
@@ -88,22 +122,31 @@ fn main() -> wry::Result<()> {
         }
     };
 
-    let slug = slugify!(&config.name);
-    let tempdir = std::env::temp_dir().join(format!("runz/{}", slug)).clean();
+    let tempdir = config.tempdir();
     std::fs::create_dir_all(&tempdir)?;
-    println!("Logging to directory: {}", tempdir.display());
 
-    let args: Vec<String> = env::args().collect();
     // Se ainda não foi relançado como --child
-    if !args.contains(&"--child".to_string()) {
-        #[cfg(unix)]
+    #[cfg(unix)]
+    if config.detach && !cli::args().child && !cli::args().foreground {
+        println!("Logging to directory: {}", tempdir.display());
         detach::detach_background(&tempdir).unwrap();
     }
 
+    let start_all_item = MenuItem::with_id("start_all_terminals", "Start All", true, None);
+    let stop_all_item = MenuItem::with_id("stop_all_terminals", "Stop All", true, None);
+    let restart_all_item = MenuItem::with_id("restart_all_terminals", "Restart All", true, None);
     let quit_item = PredefinedMenuItem::quit(Some("Quit"));
     let separator = PredefinedMenuItem::separator();
     let submenu = Submenu::new("File", true);
-    submenu.append_items(&[&separator, &quit_item]).unwrap();
+    submenu
+        .append_items(&[
+            &start_all_item,
+            &stop_all_item,
+            &restart_all_item,
+            &separator,
+            &quit_item,
+        ])
+        .unwrap();
     let menu = Menu::new();
     menu.append_items(&[&submenu]).unwrap();
 
@@ -132,6 +175,32 @@ fn main() -> wry::Result<()> {
     // Setup global emitter
     let (tx, rx) = mpsc::channel::<(String, serde_json::Value)>();
     emitter::set_emitter(&tx);
+    stats::start();
+    endpoints::start();
+
+    #[cfg(unix)]
+    if let Err(err) = control::serve(&tempdir) {
+        eprintln!("Control socket unavailable: {}", err);
+    }
+
+    if let Some(addr) = cli::args().serve {
+        remote::start();
+        match server::start(addr, &[server::Role::Operator, server::Role::Viewer], true) {
+            Ok(served) => {
+                for (role, url) in &served.urls {
+                    println!("Remote {} access: {}", role.name(), url);
+                }
+            }
+            Err(err) => eprintln!("Remote access unavailable: {}", err),
+        }
+    }
+
+    if let Some(addr) = cli::args().metrics {
+        match metrics::serve(addr) {
+            Ok(()) => println!("Metrics available at http://{}/metrics", addr),
+            Err(err) => eprintln!("Metrics unavailable: {}", err),
+        }
+    }
 
     let init_script = format!(
         "
@@ -144,71 +213,30 @@ fn main() -> wry::Result<()> {
     let url = if cfg!(debug_assertions) {
         "http://localhost:5173".to_string()
     } else {
-        // Channel to get the selected port
-        let (url_tx, url_rx) = mpsc::channel::<String>();
-
-        // Spawn a thread for the tiny_http server
-        thread::spawn(move || {
-            // Let tiny_http bind to a random open port
-            let server = Server::http("127.0.0.1:0").expect("Failed to bind server");
-
-            // Get the address and port
-            let addr = server.server_addr();
-            let url = format!("http://{}", addr.to_string());
-            url_tx.send(url).expect("Failed to send port");
-
-            for request in server.incoming_requests() {
-                let response = Response::from_string(include_str!("../frontend/dist/index.html"))
-                    .with_header(Header::from_bytes(&b"Content-Type"[..], &b"text/html"[..]).unwrap());
-                let _ = request.respond(response);
+        match cli::args().listen {
+            Some(addr) => {
+                let served = server::start(addr, &[server::Role::Operator], false)
+                    .expect("Failed to start the frontend server");
+                let url = served.url(server::Role::Operator).unwrap().to_string();
+                // Chosen on purpose, probably to be opened somewhere else too
+                println!("UI available at {}", url);
+                url
             }
-        });
-
-        // Wait for the server to tell us the random port
-        let url = url_rx.recv().expect("Failed to receive port");
-        url
+            None => assets::PROTOCOL_URL.to_string(),
+        }
     };
 
     let webview = WebViewBuilder::new()
         .with_url(url)
         .with_devtools(true)
         .with_initialization_script(init_script)
+        .with_custom_protocol("runz".to_string(), |_id, request| {
+            assets::response(&request, false)
+        })
         .with_ipc_handler(move |message| {
-            let msg: IpcMsg = serde_json::from_str(message.body()).unwrap();
-            match msg.event.name.as_str() {
-                "open_link" => {
-                    if let Some(payload) = msg.event.payload {
-                        cmd::open_link(payload.to_string()).unwrap();
-                    }
-                }
-                "start_terminal" => {
-                    cmd::start_terminal(msg.id).unwrap();
-                }
-                "stop_terminal" => {
-                    cmd::stop_terminal(msg.id).unwrap();
-                }
-                "input_terminal" => {
-                    if let Some(payload) = msg.event.payload {
-                        cmd::input_terminal(msg.id, payload.to_string()).unwrap();
-                    }
-                }
-                "resize_terminal" => {
-                    if let Some(payload) = msg.event.payload {
-                        if let Ok(payload) = serde_json::from_value::<ResizePayload>(payload) {
-                            cmd::resize_terminal(msg.id, payload.cols, payload.rows).unwrap();
-                        }
-                    }
-                }
-                "close_window" => {
-                    emitter::emit("close_window".to_string(), json!({}));
-                }
-                "minimize_window" => {
-                    emitter::emit("minimize_window".to_string(), json!({}));
-                }
-                "maximize_window" => {
-                    emitter::emit("maximize_window".to_string(), json!({}));
-                }
-                _ => {}
+            // Anything that isn't a command is ignored rather than crashing the app
+            if let Ok(msg) = serde_json::from_str::<ipc::IpcMsg>(message.body()) {
+                ipc::handle(msg);
             }
         }).build(&window)?;
 
@@ -238,6 +266,18 @@ fn main() -> wry::Result<()> {
             webview.evaluate_script(&script).unwrap();
         }
 
+        // Menu items
+        if let Event::MainEventsCleared = event {
+            while let Ok(menu_event) = MenuEvent::receiver().try_recv() {
+                match menu_event.id().as_ref() {
+                    "start_all_terminals" => cmd::start_all_terminals().unwrap(),
+                    "stop_all_terminals" => cmd::stop_all_terminals().unwrap(),
+                    "restart_all_terminals" => cmd::restart_all_terminals().unwrap(),
+                    _ => {}
+                }
+            }
+        }
+
         // Emitter logic
         if let Event::MainEventsCleared = event {
             while let Ok((evt, payload)) = rx.try_recv() {
@@ -0,0 +1,168 @@
+use std::fmt::{Display, Write};
+use std::net::SocketAddr;
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::{
+    config::{self, Config},
+    endpoints, ports,
+    pty_manager::{self, RunState},
+    replicas, stats,
+};
+
+// The Prometheus text format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// One metric with its samples, which have to be listed together
+struct Metric {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    samples: String,
+}
+
+impl Metric {
+    fn new(name: &'static str, kind: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            kind,
+            help,
+            samples: String::new(),
+        }
+    }
+
+    fn add(&mut self, labels: &str, value: impl Display) {
+        let _ = writeln!(self.samples, "{}{{{}}} {}", self.name, labels, value);
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "# HELP {} {}", self.name, self.help)?;
+        writeln!(f, "# TYPE {} {}", self.name, self.kind)?;
+        f.write_str(&self.samples)
+    }
+}
+
+// Escapes a label value, quoted
+fn label_value(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+    format!("\"{escaped}\"")
+}
+
+/// The state of every service, in the Prometheus text format. CPU and memory
+/// are only known on Linux, and only while the service runs.
+pub fn render(config: &Config) -> String {
+    let mut up = Metric::new(
+        "runz_service_up",
+        "gauge",
+        "Whether the service's process is running",
+    );
+    let mut paused = Metric::new(
+        "runz_service_paused",
+        "gauge",
+        "Whether the service's processes are frozen until resumed",
+    );
+    let mut ready = Metric::new(
+        "runz_service_ready",
+        "gauge",
+        "Whether the service listens on its declared ports, or the task succeeded",
+    );
+    let mut restarts = Metric::new(
+        "runz_service_restarts_total",
+        "counter",
+        "How many times the service was spawned again",
+    );
+    let mut exit_code = Metric::new(
+        "runz_service_last_exit_code",
+        "gauge",
+        "Exit code of the service's last process",
+    );
+    let mut output = Metric::new(
+        "runz_service_output_bytes_total",
+        "counter",
+        "Output the service produced, in bytes",
+    );
+    let mut uptime = Metric::new(
+        "runz_service_uptime_seconds",
+        "gauge",
+        "How long the service's process has been running",
+    );
+    let mut cpu = Metric::new(
+        "runz_service_cpu_seconds_total",
+        "counter",
+        "CPU time used by the service's processes",
+    );
+    let mut memory = Metric::new(
+        "runz_service_resident_memory_bytes",
+        "gauge",
+        "Resident memory of the service's processes",
+    );
+
+    let snapshot = stats::Snapshot::take();
+    let sockets = endpoints::listening_sockets();
+    for service in &replicas::expand(config) {
+        let labels = format!(
+            "id={},name={}",
+            label_value(&service.id),
+            label_value(&service.name)
+        );
+        let status = pty_manager::status(&service.id);
+
+        up.add(&labels, u8::from(status.pid.is_some()));
+        paused.add(&labels, u8::from(status.state == RunState::Paused));
+        ready.add(
+            &labels,
+            u8::from(ports::is_ready(service, &snapshot, &sockets)),
+        );
+        restarts.add(&labels, status.restarts);
+        output.add(&labels, status.output_bytes);
+        if let Some(code) = status.exit_code {
+            exit_code.add(&labels, code);
+        }
+        if let Some(started_at) = status.started_at {
+            uptime.add(&labels, started_at.elapsed().as_secs_f64());
+        }
+        if let Some(usage) = status.pid.and_then(|pid| snapshot.usage(pid)) {
+            cpu.add(&labels, usage.cpu_time);
+            memory.add(&labels, usage.memory);
+        }
+    }
+
+    [
+        up, paused, ready, restarts, exit_code, output, uptime, cpu, memory,
+    ]
+    .iter()
+    .map(|metric| metric.to_string())
+    .collect()
+}
+
+/// Serves the metrics of every service on `addr`, at /metrics.
+pub fn serve(addr: SocketAddr) -> Result<(), String> {
+    let server = Server::http(addr).map_err(|e| format!("failed to listen on {addr}: {e}"))?;
+
+    if !addr.ip().is_loopback() {
+        eprintln!("Serving metrics on {addr}, which other machines may reach");
+    }
+
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let path = request.url().split('?').next().unwrap_or_default();
+            let found =
+                matches!(request.method(), Method::Get | Method::Head) && path == "/metrics";
+
+            let response = match config::get_config() {
+                Ok(config) if found => Response::from_string(render(config)).with_header(
+                    Header::from_bytes(&b"Content-Type"[..], CONTENT_TYPE.as_bytes()).unwrap(),
+                ),
+                Ok(_) => Response::from_string("Not Found").with_status_code(404),
+                Err(e) => Response::from_string(e.to_string()).with_status_code(500),
+            };
+            let _ = request.respond(response);
+        }
+    });
+
+    Ok(())
+}
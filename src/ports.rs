@@ -0,0 +1,289 @@
+use parking_lot::Mutex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Sender};
+use std::time::Duration;
+
+use crate::{
+    config::{ServiceConfig, ServiceKind},
+    emitter, endpoints,
+    pty_manager::{self, RunState},
+    remote,
+    stats::Snapshot,
+};
+
+// How long a conflict waits for an answer before the start is aborted
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(300);
+
+// How long a killed process gets to let go of its port
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What to do about a port that's already taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Choice {
+    /// Terminate the process holding the port, then start
+    Kill,
+    /// Start regardless, the service may pick another port or fail by itself
+    #[serde(rename = "start")]
+    StartAnyway,
+    /// Don't start
+    Abort,
+}
+
+impl Choice {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "kill" => Some(Choice::Kill),
+            "start" => Some(Choice::StartAnyway),
+            "abort" => Some(Choice::Abort),
+            _ => None,
+        }
+    }
+}
+
+/// A process listening on a port.
+#[derive(Debug, Clone)]
+pub struct Owner {
+    pub pid: u32,
+    pub name: Option<String>,
+    // The service it belongs to, when runz runs it
+    pub service: Option<ServiceConfig>,
+}
+
+impl std::fmt::Display for Owner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.service, &self.name) {
+            (Some(service), _) => write!(f, "service '{}' (pid {})", service.name, self.pid),
+            (None, Some(name)) => write!(f, "{} (pid {})", name, self.pid),
+            (None, None) => write!(f, "pid {}", self.pid),
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    // Conflicts waiting for the frontend to answer, by service id
+    static ref PROMPTS: Mutex<HashMap<String, Sender<Choice>>> = Mutex::new(HashMap::new());
+}
+
+/// Whether something already listens on `port`. Both the loopback and the
+/// wildcard address are tried, as a listener on either one may not block
+/// binding the other. A port runz isn't allowed to bind, such as one below
+/// 1024 without root, is looked up among the listening sockets instead, and
+/// is an error where those can't be listed.
+pub fn in_use(port: u16) -> Result<bool, String> {
+    let mut denied = false;
+    for host in ["127.0.0.1", "0.0.0.0"] {
+        match TcpListener::bind((host, port)) {
+            Err(e) if e.kind() == ErrorKind::AddrInUse => return Ok(true),
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => denied = true,
+            _ => {}
+        }
+    }
+    if !denied {
+        return Ok(false);
+    }
+    listed(port)
+        .ok_or_else(|| format!("not allowed to bind port {port} to check whether it's free"))
+}
+
+#[cfg(target_os = "linux")]
+fn listed(port: u16) -> Option<bool> {
+    Some(
+        endpoints::listening_sockets()
+            .values()
+            .any(|listening| *listening == port),
+    )
+}
+
+#[cfg(not(target_os = "linux"))]
+fn listed(_port: u16) -> Option<bool> {
+    None
+}
+
+/// Whether a service is up: running and listening on every port it declares.
+/// Tasks are ready once they succeeded. A paused service isn't ready, as it
+/// can't answer, but it hasn't failed either. Its processes and ports are
+/// looked up in `snapshot` and `sockets`, see `endpoints::listening_ports`.
+pub fn is_ready(service: &ServiceConfig, snapshot: &Snapshot, sockets: &HashMap<u64, u16>) -> bool {
+    let status = pty_manager::status(&service.id);
+    match (service.kind, status.state, status.pid) {
+        (ServiceKind::Task, state, _) => state == RunState::Succeeded,
+        (ServiceKind::Service, RunState::Running, Some(pid)) => {
+            listens_on(pid, &service.ports, snapshot, sockets)
+        }
+        (ServiceKind::Service, _, _) => false,
+    }
+}
+
+// On Linux the ports must belong to the service's own processes
+#[cfg(target_os = "linux")]
+fn listens_on(pid: u32, ports: &[u16], snapshot: &Snapshot, sockets: &HashMap<u64, u16>) -> bool {
+    if ports.is_empty() {
+        return true;
+    }
+    let listening = endpoints::listening_ports(pid, snapshot, sockets);
+    ports.iter().all(|port| listening.contains(port))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn listens_on(
+    _pid: u32,
+    ports: &[u16],
+    _snapshot: &Snapshot,
+    _sockets: &HashMap<u64, u16>,
+) -> bool {
+    ports.iter().all(|port| in_use(*port) == Ok(true))
+}
+
+/// The process listening on `port`, found through /proc on Linux.
+pub fn owner(port: u16) -> Option<Owner> {
+    let pid = endpoints::port_owner(port)?;
+    Some(Owner {
+        pid,
+        name: process_name(pid),
+        service: managing_service(pid),
+    })
+}
+
+// The running service `pid` is one of the processes of
+#[cfg(target_os = "linux")]
+fn managing_service(pid: u32) -> Option<ServiceConfig> {
+    let snapshot = Snapshot::take();
+    let session = pty_manager::sessions().into_iter().find(|session| {
+        session
+            .pid
+            .is_some_and(|root| snapshot.tree(root).contains(&pid))
+    })?;
+    let config = crate::config::get_config().ok()?;
+    crate::replicas::find(config, &session.id)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn managing_service(_pid: u32) -> Option<ServiceConfig> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn process_name(pid: u32) -> Option<String> {
+    let name = std::fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+    Some(name.trim().to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_name(_pid: u32) -> Option<String> {
+    None
+}
+
+/// Makes sure the ports a service declares are free before it's spawned.
+/// What to do about each one that isn't comes from the service's
+/// `on_port_conflict`, or else is asked to the frontend. Blocks until
+/// answered, so it must not run on the event loop.
+pub fn preflight(service: &ServiceConfig) -> Result<(), String> {
+    for &port in &service.ports {
+        match in_use(port) {
+            Ok(true) => {}
+            Ok(false) => continue,
+            // The service may be allowed to bind it, let it find out
+            Err(e) => {
+                pty_manager::emit_banner(&service.id, &format!("{e}, starting anyway"));
+                continue;
+            }
+        }
+
+        let owner = owner(port);
+        let holder = owner
+            .as_ref()
+            .map(|owner| owner.to_string())
+            .unwrap_or_else(|| "another process".to_string());
+        pty_manager::emit_banner(
+            &service.id,
+            &format!("port {port} is already in use by {holder}"),
+        );
+
+        let choice = match service.on_port_conflict {
+            Some(choice) => choice,
+            None => ask(&service.id, port, owner.as_ref())?,
+        };
+        match choice {
+            Choice::Kill => match owner {
+                Some(owner) => kill(&owner, port)?,
+                None => return Err(format!("can't tell which process holds port {port}")),
+            },
+            Choice::StartAnyway => {}
+            Choice::Abort => return Err(format!("port {port} is in use")),
+        }
+    }
+    Ok(())
+}
+
+// Sends a `port_conflict` event and waits for the answer, aborting if none
+// comes in time. Fails right away when no window or remote operator could
+// answer, instead of holding the start up.
+fn ask(id: &str, port: u16, owner: Option<&Owner>) -> Result<Choice, String> {
+    if !emitter::has_window() && !remote::has_operators() {
+        return Err(format!(
+            "port {port} is in use and nobody can be asked, set on_port_conflict"
+        ));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    PROMPTS.lock().insert(id.to_string(), tx);
+
+    emitter::emit(
+        id.to_string(),
+        serde_json::json!({
+            "type": "port_conflict",
+            "data": {
+                "port": port,
+                "pid": owner.map(|owner| owner.pid),
+                "process": owner.and_then(|owner| owner.name.clone()),
+            },
+        }),
+    );
+
+    let choice = rx.recv_timeout(PROMPT_TIMEOUT).unwrap_or(Choice::Abort);
+    PROMPTS.lock().remove(id);
+    Ok(choice)
+}
+
+/// Answers the pending port conflict of a service with `kill`, `start` or
+/// `abort`.
+pub fn resolve(id: &str, choice: &str) -> Result<(), String> {
+    let choice = Choice::parse(choice).ok_or_else(|| format!("unknown choice '{choice}'"))?;
+    if let Some(tx) = PROMPTS.lock().get(id) {
+        tx.send(choice).ok();
+    }
+    Ok(())
+}
+
+// Terminates the owner and waits for the port to be released. Services runz
+// runs are stopped like any other stop, hooks included.
+#[cfg(unix)]
+fn kill(owner: &Owner, port: u16) -> Result<(), String> {
+    if let Some(service) = &owner.service {
+        pty_manager::stop_pty(&service.id)?;
+    } else if unsafe { libc::kill(owner.pid as i32, libc::SIGTERM) } != 0 {
+        return Err(format!(
+            "failed to kill {owner}: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    let deadline = std::time::Instant::now() + KILL_TIMEOUT;
+    while in_use(port) == Ok(true) {
+        if std::time::Instant::now() >= deadline {
+            return Err(format!("port {port} is still in use after killing {owner}"));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn kill(owner: &Owner, _port: u16) -> Result<(), String> {
+    Err(format!("killing {owner} is not supported on this platform"))
+}
@@ -1,22 +1,49 @@
-use parking_lot::Mutex;
-use portable_pty::{native_pty_system, Child, CommandBuilder, PtyPair, PtySize};
-use std::{collections::HashMap, sync::Arc};
+use parking_lot::{Condvar, Mutex};
+use portable_pty::{native_pty_system, Child, CommandBuilder, ExitStatus, MasterPty, PtySize};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use crate::{config::ServiceConfig, emitter};
+use crate::{
+    config::ServiceConfig,
+    emitter,
+    endpoints::{self, OutputScanner},
+    hooks::{self, Hook},
+    ports,
+};
 
-/// Represents a session containing both the PTY pair and the spawned child process.
+/// Represents a session containing both the PTY master and the spawned child process.
 pub struct PtySession {
-    // The PTY pair used for I/O.
-    pub child: PtyPair,
+    // The PTY master used for I/O. The slave side is only held by the child,
+    // so reads hit EOF once the child is gone.
+    pub master: Box<dyn MasterPty + Send>,
     // The process handle returned when spawning the command on the PTY.
     pub process: Box<dyn Child + Send + Sync>,
     pub is_running: bool,
+    // The service this session was spawned for.
+    pub service: ServiceConfig,
+    pub pid: Option<u32>,
+    pub started_at: Instant,
+    // How many times the service was spawned before this session
+    pub restarts: u32,
+    // Frozen with SIGSTOP, the processes are still there
+    pub paused: bool,
 }
 
 impl PtySession {
     /// Stops the session by killing the child process.
     fn stop(&mut self) {
         if self.is_running {
+            // A stopped process only acts on the hangup once it's continued,
+            // and kill() doesn't wait long before resorting to SIGKILL
+            if self.paused {
+                if let Some(pid) = self.pid {
+                    freeze(pid, false).ok();
+                }
+                self.paused = false;
+            }
             // Use the process handle to kill the running process.
             self.process.kill().ok();
             self.is_running = false;
@@ -24,16 +51,58 @@ impl PtySession {
     }
 }
 
+/// Where a service stands, as far as its last run is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunState {
+    /// Never started, or started again and not spawned yet
+    Pending,
+    Running,
+    /// Running, but frozen until resumed
+    Paused,
+    /// Exited with a zero code
+    Succeeded,
+    /// Exited with a non-zero code or was killed
+    Failed,
+}
+
+impl RunState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RunState::Pending => "pending",
+            RunState::Running => "running",
+            RunState::Paused => "paused",
+            RunState::Succeeded => "succeeded",
+            RunState::Failed => "failed",
+        }
+    }
+}
+
 /// Global structure to manage multiple PTY sessions.
 #[derive(Default)]
 pub struct GlobalPty {
     pub sessions: HashMap<String, PtySession>,
+    // How each service's last process exited
+    pub exits: HashMap<String, ExitStatus>,
+    // Services claimed for a start, waiting on their dependencies or running
+    // their pre_start hook, not spawned yet
+    pub starting: HashSet<String>,
+    // Last size the frontend gave each terminal, reused when it's spawned again
+    pub sizes: HashMap<String, PtySize>,
+    // How many times each service was spawned
+    pub launches: HashMap<String, u32>,
+    // Bytes of output each service produced, over all of its runs
+    pub output_bytes: HashMap<String, u64>,
 }
 
 impl GlobalPty {
     pub fn new() -> Self {
         Self {
             sessions: HashMap::new(),
+            exits: HashMap::new(),
+            starting: HashSet::new(),
+            sizes: HashMap::new(),
+            launches: HashMap::new(),
+            output_bytes: HashMap::new(),
         }
     }
 }
@@ -41,59 +110,143 @@ impl GlobalPty {
 // Store in a static to access from anywhere
 lazy_static::lazy_static! {
     pub static ref PTY_MANAGER: Arc<Mutex<GlobalPty>> = Arc::new(Mutex::new(GlobalPty::new()));
+    // Notified whenever a session is removed, after its process was reaped
+    static ref SESSION_CLOSED: Condvar = Condvar::new();
 }
 
 pub fn spawn_pty(service: &ServiceConfig) -> Result<(), String> {
+    {
+        let mut manager = PTY_MANAGER.lock();
+
+        if manager.sessions.contains_key(&service.id) {
+            // Already exists, so just notify "running"
+            emitter::emit(service.id.clone(), serde_json::json!({ "type": "running" }));
+            return Ok(());
+        }
+
+        // Someone else is already starting it
+        if !manager.starting.insert(service.id.clone()) {
+            return Ok(());
+        }
+    }
+
+    spawn_claimed(service)
+}
+
+/// Claims the start of a service if `should_start` accepts its state, so that
+/// of several callers only one goes on to spawn it with `spawn_claimed`.
+/// Claimed services count as running.
+pub fn claim_start(id: &str, should_start: impl FnOnce(RunState) -> bool) -> bool {
     let mut manager = PTY_MANAGER.lock();
+    should_start(state_of(&manager, id)) && manager.starting.insert(id.to_string())
+}
 
-    if manager.sessions.contains_key(&service.id) {
-        // Already exists, so just notify "running"
-        emitter::emit(service.id.clone(), serde_json::json!({ "type": "running" }));
-        return Ok(());
+/// Gives up a claimed start, recording it as failed so that dependents
+/// waiting on the service stop waiting.
+pub fn abandon_start(id: &str) {
+    let mut manager = PTY_MANAGER.lock();
+    if manager.starting.remove(id) {
+        manager.exits.insert(id.to_string(), ExitStatus::with_exit_code(1));
     }
+}
+
+// A spawned process, with the PTY it runs on and a reader of its output
+type Spawned = (
+    Box<dyn MasterPty + Send>,
+    Box<dyn Child + Send + Sync>,
+    Box<dyn std::io::Read + Send>,
+);
 
+// Starts the service's program on a new PTY of the given size
+fn spawn_child(service: &ServiceConfig, size: PtySize) -> Result<Spawned, String> {
     let pty_system = native_pty_system();
     let mut builder = CommandBuilder::new(&service.prog);
 
     builder.env("PWD", &service.wdir);
+    for (key, value) in &service.env {
+        builder.env(key, value);
+    }
     builder.cwd(&service.wdir);
     builder.args(&service.args);
 
-    let pair = pty_system
-        .openpty(PtySize {
-            rows: 24,
-            cols: 80,
-            pixel_width: 0,
-            pixel_height: 0,
-        })
-        .map_err(|e| e.to_string())?;
+    let pair = pty_system.openpty(size).map_err(|e| e.to_string())?;
 
-    let child = pair
+    let mut child = pair
         .slave
         .spawn_command(builder)
         .map_err(|e| format!("spawn error: {e}"))?;
 
+    match pair.master.try_clone_reader() {
+        Ok(reader) => Ok((pair.master, child, reader)),
+        Err(e) => {
+            child.kill().ok();
+            Err(format!("failed to read the terminal: {e}"))
+        }
+    }
+}
+
+/// Spawns a service claimed with `claim_start`.
+pub fn spawn_claimed(service: &ServiceConfig) -> Result<(), String> {
+    // Run outside of the lock, hooks and port conflicts may take a while
+    let hook_result =
+        hooks::run_hook(service, Hook::PreStart).and_then(|_| ports::preflight(service));
+
+    let mut manager = PTY_MANAGER.lock();
+    manager.starting.remove(&service.id);
+
+    let size = manager.sizes.get(&service.id).copied().unwrap_or(PtySize {
+        rows: 24,
+        cols: 80,
+        pixel_width: 0,
+        pixel_height: 0,
+    });
+
+    let (master, child, mut reader) = match hook_result.and_then(|_| spawn_child(service, size)) {
+        Ok(spawned) => spawned,
+        Err(e) => {
+            // Anyone waiting on this service needs to know it won't come up
+            manager.exits.insert(service.id.clone(), ExitStatus::with_exit_code(1));
+            emit_banner(&service.id, &format!("{e}, not starting"));
+            return Err(e);
+        }
+    };
+
+    let launches = manager.launches.entry(service.id.clone()).or_insert(0);
+    *launches += 1;
+
     let session = PtySession {
-        child: pair,
+        master,
+        pid: child.process_id(),
         process: child,
         is_running: true,
+        service: service.clone(),
+        started_at: Instant::now(),
+        restarts: *launches - 1,
+        paused: false,
     };
 
-    // clone reader
-    let mut master = session.child.master.try_clone_reader().unwrap();
     let id_clone = service.id.clone();
 
+    endpoints::reset(&service.id);
+    let mut scanner = OutputScanner::new(&service.id);
+
     std::thread::spawn(move || {
         let mut buf = [0u8; 1024];
         loop {
-            match master.read(&mut buf) {
+            match reader.read(&mut buf) {
                 // EOL
                 Ok(0) => {
                     emitter::emit(id_clone.clone(), serde_json::json!({ "type": "stopped" }));
                     break;
                 }
                 Ok(n) => {
+                    *PTY_MANAGER
+                        .lock()
+                        .output_bytes
+                        .entry(id_clone.clone())
+                        .or_insert(0) += n as u64;
                     let output = String::from_utf8_lossy(&buf[..n]).to_string();
+                    scanner.feed(&output);
                     emitter::emit(
                         id_clone.clone(),
                         serde_json::json!({ "type": "output", "data": output }),
@@ -109,19 +262,57 @@ pub fn spawn_pty(service: &ServiceConfig) -> Result<(), String> {
             }
         }
 
+        // Reap the process before letting go of the session, so nothing can
+        // spawn the service again while the old process is still around
+        let status = loop {
+            let mut manager = PTY_MANAGER.lock();
+            let Some(session) = manager.sessions.get_mut(&id_clone) else {
+                // Cleared by cleanup_all, which takes care of everything
+                return;
+            };
+            match session.process.try_wait() {
+                Ok(Some(status)) => break Some(status),
+                Ok(None) => {}
+                Err(_) => break None,
+            }
+            drop(manager);
+            std::thread::sleep(Duration::from_millis(20));
+        };
+
         let mut manager = PTY_MANAGER.lock();
-        if let Some(s) = manager.sessions.get_mut(&id_clone) {
-            s.is_running = false;
+        let session = manager.sessions.remove(&id_clone);
+        if let Some(status) = &status {
+            manager.exits.insert(id_clone.clone(), status.clone());
+        }
+        drop(manager);
+        SESSION_CLOSED.notify_all();
+
+        if let Some(status) = status {
+            emitter::emit(
+                id_clone.clone(),
+                serde_json::json!({
+                    "type": "exited",
+                    "data": { "code": status.exit_code(), "success": status.success() },
+                }),
+            );
+        }
+        if let Some(session) = session {
+            hooks::run_hook_reporting(&session.service, Hook::PostStop);
         }
-        manager.sessions.remove(&id_clone);
     });
 
     // Store the session
+    manager.exits.remove(&service.id);
     manager.sessions.insert(service.id.clone(), session);
 
     // Notify the frontend that the session has started
     emitter::emit(service.id.clone(), serde_json::json!({ "type": "running" }));
 
+    if service.hooks.post_start.is_some() {
+        let service = service.clone();
+        std::thread::spawn(move || hooks::run_post_start(&service));
+    }
+
     Ok(())
 }
 
@@ -131,7 +322,6 @@ pub fn write_input(id: &str, data: &str) -> Result<(), String> {
         if session.is_running {
             // Get a writer from the master PTY
             let mut writer = session
-                .child
                 .master
                 .take_writer()
                 .map_err(|e| format!("failed to get writer: {}", e))?;
@@ -148,17 +338,19 @@ pub fn write_input(id: &str, data: &str) -> Result<(), String> {
 }
 
 pub fn resize_pty(id: &str, cols: u16, rows: u16) -> Result<(), String> {
-    let manager = PTY_MANAGER.lock();
+    let mut manager = PTY_MANAGER.lock();
+    let size = PtySize {
+        cols,
+        rows,
+        pixel_width: 0,
+        pixel_height: 0,
+    };
+    manager.sizes.insert(id.to_string(), size);
+
     if let Some(session) = manager.sessions.get(&id.to_string()) {
         session
-            .child
             .master
-            .resize(PtySize {
-                cols,
-                rows,
-                pixel_width: 0,
-                pixel_height: 0,
-            })
+            .resize(size)
             .map_err(|e| e.to_string())?;
         Ok(())
     } else {
@@ -168,19 +360,357 @@ pub fn resize_pty(id: &str, cols: u16, rows: u16) -> Result<(), String> {
 }
 
 pub fn stop_pty(id: &str) -> Result<(), String> {
+    let service = match PTY_MANAGER.lock().sessions.get(id) {
+        Some(s) if s.is_running => s.service.clone(),
+        _ => return Ok(()),
+    };
+
+    // Run outside of the lock, hooks may take a while
+    hooks::run_hook_reporting(&service, Hook::PreStop);
+
     let mut manager = PTY_MANAGER.lock();
-    if let Some(s) = manager.sessions.get_mut(&id.to_string()) {
+    if let Some(s) = manager.sessions.get_mut(id) {
         s.stop();
     }
 
     Ok(())
 }
 
+fn state_of(manager: &GlobalPty, id: &str) -> RunState {
+    if manager.sessions.get(id).is_some_and(|session| session.paused) {
+        return RunState::Paused;
+    }
+    if manager.sessions.contains_key(id) || manager.starting.contains(id) {
+        return RunState::Running;
+    }
+    match manager.exits.get(id) {
+        Some(status) if status.success() => RunState::Succeeded,
+        Some(_) => RunState::Failed,
+        None => RunState::Pending,
+    }
+}
+
+pub fn run_state(id: &str) -> RunState {
+    state_of(&PTY_MANAGER.lock(), id)
+}
+
+/// Everything the manager knows about a service, whether it runs or not.
+pub struct Status {
+    pub state: RunState,
+    // Set while its process runs
+    pub pid: Option<u32>,
+    pub started_at: Option<Instant>,
+    // How many times it was spawned again after its first run
+    pub restarts: u32,
+    // Exit code of its last process, once that exited
+    pub exit_code: Option<u32>,
+    pub output_bytes: u64,
+}
+
+pub fn status(id: &str) -> Status {
+    let manager = PTY_MANAGER.lock();
+    let session = manager.sessions.get(id).filter(|session| session.is_running);
+
+    Status {
+        state: state_of(&manager, id),
+        pid: session.and_then(|session| session.pid),
+        started_at: session.map(|session| session.started_at),
+        restarts: manager.launches.get(id).map_or(0, |launches| launches.saturating_sub(1)),
+        exit_code: manager.exits.get(id).map(|status| status.exit_code()),
+        output_bytes: manager.output_bytes.get(id).copied().unwrap_or(0),
+    }
+}
+
+/// A running session, as seen from outside of the manager.
+pub struct SessionInfo {
+    pub id: String,
+    pub pid: Option<u32>,
+    pub started_at: Instant,
+    pub restarts: u32,
+}
+
+pub fn sessions() -> Vec<SessionInfo> {
+    PTY_MANAGER
+        .lock()
+        .sessions
+        .iter()
+        .filter(|(_, session)| session.is_running)
+        .map(|(id, session)| SessionInfo {
+            id: id.clone(),
+            pid: session.pid,
+            started_at: session.started_at,
+            restarts: session.restarts,
+        })
+        .collect()
+}
+
+pub fn is_running(id: &str) -> bool {
+    let manager = PTY_MANAGER.lock();
+    manager
+        .sessions
+        .get(id)
+        .map(|session| session.is_running)
+        .unwrap_or(false)
+}
+
+/// Blocks until the service has no session left, meaning its process was
+/// reaped. Returns false if that didn't happen before the timeout.
+pub fn wait_stopped(id: &str, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut manager = PTY_MANAGER.lock();
+    while manager.sessions.contains_key(id) {
+        if SESSION_CLOSED.wait_until(&mut manager, deadline).timed_out() {
+            return !manager.sessions.contains_key(id);
+        }
+    }
+    true
+}
+
+/// Stops the service, waits for its old process to be reaped and spawns it
+/// again with the same terminal size.
+pub fn restart_pty(service: &ServiceConfig) -> Result<(), String> {
+    stop_pty(&service.id)?;
+
+    if !wait_stopped(&service.id, Duration::from_secs(10)) {
+        return Err(format!("timed out waiting for '{}' to stop", service.name));
+    }
+
+    spawn_pty(service)
+}
+
+/// Delivers a signal to the process group of a running session. Stopping
+/// and continuing it pauses and resumes the service, the way `pause_pty` and
+/// `resume_pty` do, so its state follows.
+pub fn signal_pty(id: &str, signal: i32) -> Result<(), String> {
+    #[cfg(unix)]
+    match signal {
+        libc::SIGSTOP | libc::SIGTSTP => return set_paused(id, true),
+        libc::SIGCONT => return set_paused(id, false),
+        _ => {}
+    }
+
+    let manager = PTY_MANAGER.lock();
+    if let Some(session) = manager.sessions.get(id) {
+        if !session.is_running {
+            return Ok(());
+        }
+
+        let pid = session
+            .process
+            .process_id()
+            .ok_or_else(|| "process has no pid".to_string())?;
+
+        send_signal(pid, signal)
+    } else {
+        println!("Tried to signal a non-existent session");
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn send_signal(pid: u32, signal: i32) -> Result<(), String> {
+    // Children are spawned as session leaders, so their pid is also the process group id
+    let result = unsafe { libc::kill(-(pid as i32), signal) };
+    if result != 0 {
+        return Err(format!("kill failed: {}", std::io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_signal(_pid: u32, _signal: i32) -> Result<(), String> {
+    Err("signals are not supported on this platform".to_string())
+}
+
+// Sends SIGSTOP, or SIGCONT to undo it, to the process group and, on Linux,
+// to descendants that moved to a group of their own
+#[cfg(unix)]
+fn freeze(pid: u32, paused: bool) -> Result<(), String> {
+    let signal = if paused { libc::SIGSTOP } else { libc::SIGCONT };
+    send_signal(pid, signal)?;
+
+    #[cfg(target_os = "linux")]
+    for member in crate::stats::process_tree(pid) {
+        unsafe { libc::kill(member as i32, signal) };
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn freeze(_pid: u32, _paused: bool) -> Result<(), String> {
+    Err("pausing is not supported on this platform".to_string())
+}
+
+fn set_paused(id: &str, paused: bool) -> Result<(), String> {
+    let mut manager = PTY_MANAGER.lock();
+    let Some(session) = manager.sessions.get_mut(id).filter(|s| s.is_running) else {
+        println!("Tried to pause or resume a non-existent session");
+        return Ok(());
+    };
+    if session.paused == paused {
+        return Ok(());
+    }
+
+    let pid = session
+        .pid
+        .ok_or_else(|| "process has no pid".to_string())?;
+    freeze(pid, paused)?;
+    session.paused = paused;
+
+    let event = if paused { "paused" } else { "resumed" };
+    emitter::emit(id.to_string(), serde_json::json!({ "type": event }));
+    Ok(())
+}
+
+/// Freezes a running service and everything it spawned, keeping their state
+/// until resumed.
+pub fn pause_pty(id: &str) -> Result<(), String> {
+    set_paused(id, true)
+}
+
+pub fn resume_pty(id: &str) -> Result<(), String> {
+    set_paused(id, false)
+}
+
+// The highest signal number, real-time signals included
+#[cfg(target_os = "linux")]
+fn max_signal() -> i32 {
+    libc::SIGRTMAX()
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn max_signal() -> i32 {
+    31
+}
+
+/// Maps a signal name such as `HUP`, `SIGUSR1` or `15` to its number.
+/// Numbers must be those of actual signals.
+#[cfg(unix)]
+pub fn parse_signal(name: &str) -> Option<i32> {
+    let name = name.trim().to_uppercase();
+    if let Ok(number) = name.parse::<i32>() {
+        return (1..=max_signal()).contains(&number).then_some(number);
+    }
+
+    let signal = match name.strip_prefix("SIG").unwrap_or(&name) {
+        "HUP" => libc::SIGHUP,
+        "INT" => libc::SIGINT,
+        "QUIT" => libc::SIGQUIT,
+        "KILL" => libc::SIGKILL,
+        "USR1" => libc::SIGUSR1,
+        "USR2" => libc::SIGUSR2,
+        "TERM" => libc::SIGTERM,
+        "CONT" => libc::SIGCONT,
+        "STOP" => libc::SIGSTOP,
+        "TSTP" => libc::SIGTSTP,
+        "WINCH" => libc::SIGWINCH,
+        _ => return None,
+    };
+    Some(signal)
+}
+
+#[cfg(not(unix))]
+pub fn parse_signal(_name: &str) -> Option<i32> {
+    None
+}
+
+/// Writes a highlighted runz message into the service's output.
+pub fn emit_banner(id: &str, message: &str) {
+    emitter::emit(
+        id.to_string(),
+        serde_json::json!({
+            "type": "output",
+            "data": format!("\r\n\x1b[1;33m[runz] {}\x1b[0m\r\n", message),
+        }),
+    );
+}
+
 pub fn cleanup_all() {
+    let services: Vec<ServiceConfig> = PTY_MANAGER
+        .lock()
+        .sessions
+        .values()
+        .filter(|session| session.is_running)
+        .map(|session| session.service.clone())
+        .collect();
+
+    // At once, so one slow hook doesn't hold up the others
+    std::thread::scope(|scope| {
+        for service in &services {
+            scope.spawn(|| hooks::run_hook_reporting(service, Hook::PreStop));
+        }
+    });
+
     let mut manager = PTY_MANAGER.lock();
     for (_id, session) in manager.sessions.iter_mut() {
         session.stop();
     }
     // Clear out the map so we don’t hold stale handles.
+    // Reader threads won't find their session anymore, so post_stop runs here.
     manager.sessions.clear();
+    drop(manager);
+    SESSION_CLOSED.notify_all();
+
+    for service in &services {
+        hooks::run_hook_reporting(service, Hook::PostStop);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::config::{Config, RawConfig};
+
+    #[test]
+    fn fails_a_service_whose_program_cant_spawn() {
+        let raw: RawConfig =
+            serde_yaml::from_str("name: Test\nservices:\n  - prog: runz-no-such-program\n")
+                .unwrap();
+        let mut service = Config::try_from(raw).unwrap().services.remove(0);
+        service.id = "no-such-program".to_string();
+
+        assert!(spawn_pty(&service).is_err());
+        // Dependents stop waiting instead of seeing it pending
+        assert_eq!(run_state(&service.id), RunState::Failed);
+        assert!(!PTY_MANAGER.lock().starting.contains(&service.id));
+    }
+
+    #[test]
+    fn pauses_a_service_sent_stop_signals() {
+        let raw: RawConfig =
+            serde_yaml::from_str("name: Test\nservices:\n  - prog: sleep\n    args: ['30']\n")
+                .unwrap();
+        let mut service = Config::try_from(raw).unwrap().services.remove(0);
+        service.id = "stop-signals".to_string();
+        spawn_pty(&service).unwrap();
+
+        signal_pty(&service.id, libc::SIGTSTP).unwrap();
+        assert_eq!(run_state(&service.id), RunState::Paused);
+        signal_pty(&service.id, libc::SIGCONT).unwrap();
+        assert_eq!(run_state(&service.id), RunState::Running);
+        signal_pty(&service.id, libc::SIGSTOP).unwrap();
+        assert_eq!(run_state(&service.id), RunState::Paused);
+
+        stop_pty(&service.id).unwrap();
+        assert!(wait_stopped(&service.id, Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn parses_signal_names_and_numbers() {
+        assert_eq!(parse_signal("HUP"), Some(libc::SIGHUP));
+        assert_eq!(parse_signal("SIGUSR1"), Some(libc::SIGUSR1));
+        assert_eq!(parse_signal(" term "), Some(libc::SIGTERM));
+        assert_eq!(parse_signal("sigwinch"), Some(libc::SIGWINCH));
+        assert_eq!(parse_signal("9"), Some(9));
+    }
+
+    #[test]
+    fn rejects_unknown_signals() {
+        assert_eq!(parse_signal("RELOAD"), None);
+        assert_eq!(parse_signal("SIG"), None);
+        assert_eq!(parse_signal(""), None);
+        assert_eq!(parse_signal("0"), None);
+        assert_eq!(parse_signal("-9"), None);
+        assert_eq!(parse_signal("99999"), None);
+    }
 }
@@ -0,0 +1,339 @@
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender};
+use std::time::{Duration, Instant};
+use tiny_http::ReadWrite;
+use tungstenite::protocol::{frame::coding::CloseCode, CloseFrame, Role as Side};
+use tungstenite::{Message, WebSocket};
+
+use crate::{
+    config, emitter,
+    ipc::{self, IpcMsg},
+    pty_manager::{self, RunState},
+    replicas,
+    server::Role,
+};
+
+// Output kept for each service, replayed to browsers connecting later
+const HISTORY: usize = 64 * 1024;
+
+// How long an events session waits for an event before pinging the browser,
+// to find out whether it's still there
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+// How long an events session listens to the browser before sending it the
+// events that came meanwhile
+const READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+// How long a browser may take to accept an event before it's dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Events waiting to be sent to a browser. One that falls further behind is
+// disconnected rather than queueing up output without end.
+const BACKLOG: usize = 4096;
+
+// What an operator may do. Window commands and opening links would act on
+// the machine running runz, so nobody gets those remotely.
+const OPERATOR_COMMANDS: [&str; 14] = [
+    "start_terminal",
+    "stop_terminal",
+    "restart_terminal",
+    "signal_terminal",
+    "pause_terminal",
+    "resume_terminal",
+    "scale_terminal",
+    "start_all_terminals",
+    "start_group_terminals",
+    "stop_all_terminals",
+    "restart_all_terminals",
+    "input_terminal",
+    "resize_terminal",
+    "resolve_port_conflict",
+];
+
+#[derive(Default)]
+struct Hub {
+    // Recent output of each service
+    history: HashMap<String, String>,
+    // Last known endpoints of each service
+    endpoints: HashMap<String, Value>,
+    // Connected browsers, with what they may do
+    clients: Vec<(Role, SyncSender<String>)>,
+}
+
+lazy_static::lazy_static! {
+    static ref HUB: Mutex<Hub> = Mutex::new(Hub::default());
+}
+
+fn message(id: &str, payload: &Value) -> String {
+    json!({ "id": id, "payload": payload }).to_string()
+}
+
+impl Hub {
+    fn record(&mut self, id: &str, payload: &Value) {
+        match payload["type"].as_str() {
+            Some("output") => {
+                let history = self.history.entry(id.to_string()).or_default();
+                history.push_str(payload["data"].as_str().unwrap_or_default());
+                if history.len() > HISTORY {
+                    let mut start = history.len() - HISTORY;
+                    while !history.is_char_boundary(start) {
+                        start += 1;
+                    }
+                    history.drain(..start);
+                }
+            }
+            Some("endpoints") => {
+                self.endpoints.insert(id.to_string(), payload.clone());
+            }
+            _ => {}
+        }
+    }
+
+    // What a browser connecting now needs to catch up
+    fn snapshot(&self, config: &config::Config) -> Vec<String> {
+        let mut messages = vec![];
+        for service in &replicas::expand(config) {
+            if let Some(history) = self.history.get(&service.id) {
+                let payload = json!({ "type": "output", "data": history });
+                messages.push(message(&service.id, &payload));
+            }
+            match pty_manager::run_state(&service.id) {
+                RunState::Running => {
+                    messages.push(message(&service.id, &json!({ "type": "running" })));
+                }
+                RunState::Paused => {
+                    messages.push(message(&service.id, &json!({ "type": "running" })));
+                    messages.push(message(&service.id, &json!({ "type": "paused" })));
+                }
+                _ => {}
+            }
+            if let Some(endpoints) = self.endpoints.get(&service.id) {
+                messages.push(message(&service.id, endpoints));
+            }
+        }
+        messages
+    }
+}
+
+/// Relays the events of every service to connected browsers, keeping their
+/// recent output for browsers connecting later.
+pub fn start() {
+    let events = emitter::subscribe();
+
+    std::thread::spawn(move || {
+        let Ok(config) = config::get_config() else {
+            return;
+        };
+
+        for (id, payload) in events {
+            // Window events are meant for this machine only
+            if config.find_service(replicas::base_id(&id)).is_none() {
+                continue;
+            }
+
+            let mut hub = HUB.lock();
+            hub.record(&id, &payload);
+            let message = message(&id, &payload);
+            // Dropping a lagging browser's sender ends its session
+            hub.clients
+                .retain(|(_, client)| client.try_send(message.clone()).is_ok());
+        }
+    });
+}
+
+/// Whether a browser that may answer prompts, such as port conflicts, is
+/// connected.
+pub fn has_operators() -> bool {
+    HUB.lock()
+        .clients
+        .iter()
+        .any(|(role, _)| *role == Role::Operator)
+}
+
+// The config as a browser with `role` gets to see it. Environments, command
+// lines and hooks tend to hold secrets, which viewers have no use for.
+fn shown_config(config: &config::Config, role: Role) -> config::Config {
+    let mut shown = config.clone();
+    if role == Role::Viewer {
+        for service in &mut shown.services {
+            service.prog.clear();
+            service.args.clear();
+            service.env.clear();
+            service.hooks = config::HooksConfig::default();
+        }
+    }
+    shown
+}
+
+// Runs a command a browser sent, if its role allows it
+fn run_command(msg: IpcMsg, role: Role) -> Result<(), String> {
+    if role != Role::Operator || !OPERATOR_COMMANDS.contains(&msg.event.name.as_str()) {
+        return Err(format!(
+            "'{}' is not allowed with {} access",
+            msg.event.name,
+            role.name()
+        ));
+    }
+    ipc::handle(msg);
+    Ok(())
+}
+
+/// Sends a browser the events of every service over an accepted WebSocket,
+/// after a `hello` with the config and the browser's role. The browser only
+/// answers pings and closes it, its commands come through `commands`.
+///
+/// With its `connection`, reads and writes time out, so the browser is
+/// listened to between events and one that stops answering is dropped.
+/// Without, a browser that's gone is only noticed once writing to it fails.
+pub fn events(stream: Box<dyn ReadWrite + Send>, connection: Option<TcpStream>, role: Role) {
+    let Ok(config) = config::get_config() else {
+        return;
+    };
+    let listening = connection.is_some_and(|connection| {
+        connection.set_read_timeout(Some(READ_TIMEOUT)).is_ok()
+            && connection.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok()
+    });
+    let mut socket = WebSocket::from_raw_socket(stream, Side::Server, None);
+
+    let config = replicas::current(config);
+    let shown = shown_config(&config, role);
+    let hello = json!({ "type": "hello", "role": role.name(), "config": shown });
+    if socket.send(Message::text(hello.to_string())).is_err() {
+        return;
+    }
+
+    // Registering and catching up at once, so nothing is missed or sent twice
+    let (tx, rx) = mpsc::sync_channel(BACKLOG);
+    let snapshot = {
+        let mut hub = HUB.lock();
+        hub.clients.push((role, tx));
+        hub.snapshot(&config)
+    };
+    for message in snapshot {
+        if socket.send(Message::text(message)).is_err() {
+            return;
+        }
+    }
+
+    let mut heard = Instant::now();
+    let mut pinged = Instant::now();
+    loop {
+        if listening {
+            match socket.read() {
+                // Pings are answered along with the next write
+                Ok(Message::Close(_)) => {
+                    let _ = socket.flush();
+                    return;
+                }
+                Ok(_) => heard = Instant::now(),
+                Err(tungstenite::Error::Io(e))
+                    if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(_) => return,
+            }
+            // Browsers answer pings on their own, one that doesn't is gone
+            if heard.elapsed() > PING_INTERVAL * 2 {
+                return;
+            }
+        }
+
+        // Without reads, waiting for events is what paces the loop
+        let wait = if listening {
+            Duration::ZERO
+        } else {
+            PING_INTERVAL
+        };
+        let mut messages = match rx.recv_timeout(wait) {
+            Ok(message) => vec![Message::text(message)],
+            Err(RecvTimeoutError::Timeout) => vec![],
+            Err(RecvTimeoutError::Disconnected) => {
+                let _ = socket.close(Some(CloseFrame {
+                    code: CloseCode::Again,
+                    reason: "Fell too far behind".into(),
+                }));
+                let _ = socket.flush();
+                return;
+            }
+        };
+        messages.extend(rx.try_iter().map(Message::text));
+        if pinged.elapsed() >= PING_INTERVAL {
+            messages.push(Message::Ping(Default::default()));
+            pinged = Instant::now();
+        }
+
+        for message in messages {
+            if socket.write(message).is_err() {
+                return;
+            }
+        }
+        if socket.flush().is_err() {
+            return;
+        }
+    }
+}
+
+/// Takes IPC commands from a browser over an accepted WebSocket, the way the
+/// webview sends them, until it disconnects. Refusals are sent back on it.
+pub fn commands(stream: Box<dyn ReadWrite + Send>, _connection: Option<TcpStream>, role: Role) {
+    let mut socket = WebSocket::from_raw_socket(stream, Side::Server, None);
+
+    loop {
+        match socket.read() {
+            Ok(Message::Text(text)) => {
+                let Ok(msg) = serde_json::from_str::<IpcMsg>(text.as_str()) else {
+                    continue;
+                };
+                // Refusals show up in the terminal the command was meant for
+                let id = msg.id.clone();
+                if let Err(e) = run_command(msg, role) {
+                    let error = message(&id, &json!({ "type": "error", "data": e }));
+                    if socket.send(Message::text(error)).is_err() {
+                        return;
+                    }
+                }
+            }
+            Ok(Message::Close(_)) | Err(_) => return,
+            Ok(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> config::Config {
+        let raw: config::RawConfig = serde_yaml::from_str(
+            "
+name: Test
+services:
+  - name: api
+    prog: ./api
+    args: [--token, s3cret]
+    env: { API_KEY: s3cret }
+    pre_start: ./migrate --password s3cret
+",
+        )
+        .unwrap();
+        config::Config::try_from(raw).unwrap()
+    }
+
+    #[test]
+    fn hides_secrets_from_viewers() {
+        let shown = serde_json::to_string(&shown_config(&config(), Role::Viewer)).unwrap();
+        assert!(!shown.contains("s3cret"), "{shown}");
+        assert!(!shown.contains("./api"), "{shown}");
+        assert!(shown.contains("\"name\":\"api\""), "{shown}");
+    }
+
+    #[test]
+    fn shows_operators_everything() {
+        let shown = shown_config(&config(), Role::Operator);
+        assert_eq!(shown.services[0].args, ["--token", "s3cret"]);
+        assert_eq!(shown.services[0].env["API_KEY"], "s3cret");
+        assert!(shown.services[0].hooks.pre_start.is_some());
+    }
+}
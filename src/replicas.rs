@@ -0,0 +1,141 @@
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::{
+    config::{self, Config, ServiceConfig},
+    emitter, pty_manager, scheduler,
+};
+
+// Time for the frontend to open tabs for new replicas before they start, so
+// their first output isn't missed
+const START_DELAY: Duration = Duration::from_millis(500);
+
+lazy_static::lazy_static! {
+    // Replica counts changed at runtime, by service id
+    static ref SCALED: Mutex<HashMap<String, u32>> = Mutex::new(HashMap::new());
+    // Replica counts scaling got running, by service id, locked while it
+    // starts and stops replicas
+    static ref APPLIED: Mutex<HashMap<String, u32>> = Mutex::new(HashMap::new());
+}
+
+/// How many replicas of a replicated service run now.
+pub fn count(service: &ServiceConfig) -> u32 {
+    SCALED
+        .lock()
+        .get(&service.id)
+        .copied()
+        .or(service.replicas)
+        .unwrap_or(0)
+}
+
+/// What a configured service runs as: itself, or each of its replicas.
+pub fn instances(service: &ServiceConfig) -> Vec<ServiceConfig> {
+    match service.replicas {
+        Some(_) => (1..=count(service))
+            .map(|number| service.replica(number))
+            .collect(),
+        None => vec![service.clone()],
+    }
+}
+
+/// Every service as it runs, replicated ones expanded into their replicas.
+pub fn expand(config: &Config) -> Vec<ServiceConfig> {
+    config.services.iter().flat_map(instances).collect()
+}
+
+/// The config with the current replica counts, for frontends joining late.
+pub fn current(config: &Config) -> Config {
+    let mut config = config.clone();
+    for service in config.services.iter_mut() {
+        if service.replicas.is_some() {
+            service.replicas = Some(count(service));
+        }
+    }
+    config
+}
+
+/// The configured service an id belongs to, replica ids like `2#3` included.
+pub fn base_id(id: &str) -> &str {
+    id.split_once('#').map_or(id, |(base, _)| base)
+}
+
+/// Looks up something that runs: a service that isn't replicated, or one of
+/// the replicas currently wanted.
+pub fn find(config: &Config, id: &str) -> Option<ServiceConfig> {
+    let service = config.find_service(base_id(id))?;
+    instances(service).into_iter().find(|s| s.id == id)
+}
+
+/// Changes how many replicas of a service run. New ones are started, the
+/// extra ones stopped.
+pub fn scale(id: &str, replicas: u32) -> Result<(), String> {
+    let config = config::get_config().map_err(|e| e.to_string())?;
+    let service = config
+        .find_service(id)
+        .ok_or_else(|| format!("Unknown service '{}'", id))?;
+    if service.replicas.is_none() {
+        return Err(format!("Service '{}' has no replicas", service.name));
+    }
+    service
+        .check_replicas(replicas)
+        .map_err(|e| e.to_string())?;
+
+    SCALED.lock().insert(service.id.clone(), replicas);
+    emitter::emit(
+        service.id.clone(),
+        serde_json::json!({ "type": "replicas", "data": replicas }),
+    );
+
+    let service = service.clone();
+    std::thread::spawn(move || apply(&service));
+
+    Ok(())
+}
+
+// Starts or stops replicas until as many run as are wanted. One scale is
+// applied at a time, from where the last one left off, so overlapping scales
+// don't start or stop the same replica.
+fn apply(service: &ServiceConfig) {
+    let mut applied = APPLIED.lock();
+    let before = applied
+        .get(&service.id)
+        .copied()
+        .or(service.replicas)
+        .unwrap_or(0);
+    let wanted = count(service);
+
+    // The last ones go first, each one waiting on its pre_stop hook
+    for number in (wanted + 1..=before).rev() {
+        let id = service.replica(number).id;
+        if let Err(e) = pty_manager::stop_pty(&id) {
+            emitter::emit(id, serde_json::json!({ "type": "error", "data": e }));
+        }
+    }
+
+    if wanted > before {
+        std::thread::sleep(START_DELAY);
+    }
+    for number in before + 1..=wanted {
+        let replica = service.replica(number);
+        if let Err(e) = scheduler::start_service(&replica) {
+            emitter::emit(
+                replica.id,
+                serde_json::json!({ "type": "error", "data": e }),
+            );
+        }
+    }
+
+    applied.insert(service.id.clone(), wanted);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_base_id_of_replicas() {
+        assert_eq!(base_id("2#3"), "2");
+        assert_eq!(base_id("2"), "2");
+    }
+}
@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    config::{ServiceConfig, ServiceKind},
+    emitter,
+    pty_manager::{self, RunState},
+    replicas,
+};
+
+// How long a service waits for a task it depends on to complete
+const TASK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Starts a service once everything it depends on is up: services running and
+/// tasks completed successfully. Dependencies that were not started yet are
+/// started first. Blocks until the service is spawned.
+pub fn start_service(service: &ServiceConfig) -> Result<(), String> {
+    wait_for_dependencies(service)?;
+    pty_manager::spawn_pty(service)?;
+    crate::watcher::watch_service(service)
+}
+
+// Starts a service claimed with `pty_manager::claim_start`
+fn start_claimed(service: &ServiceConfig) -> Result<(), String> {
+    if let Err(e) = wait_for_dependencies(service) {
+        pty_manager::abandon_start(&service.id);
+        return Err(e);
+    }
+    pty_manager::spawn_claimed(service)?;
+    crate::watcher::watch_service(service)
+}
+
+fn wait_for_dependencies(service: &ServiceConfig) -> Result<(), String> {
+    let config = crate::config::get_config().map_err(|e| e.to_string())?;
+
+    for id in &service.depends_on {
+        let dependency = config
+            .find_service(id)
+            .ok_or_else(|| format!("unknown dependency '{id}'"))?;
+
+        // A replicated dependency is up once all of its replicas are
+        for instance in replicas::instances(dependency) {
+            wait_for_dependency(service, &instance)?;
+        }
+    }
+    Ok(())
+}
+
+fn wait_for_dependency(service: &ServiceConfig, dependency: &ServiceConfig) -> Result<(), String> {
+    // Of several dependents, only the first one starts it. A failed dependency
+    // gets another chance when something needs it again
+    let claimed = pty_manager::claim_start(&dependency.id, |state| {
+        matches!(state, RunState::Pending | RunState::Failed)
+    });
+    if claimed {
+        start_claimed(dependency)?;
+    }
+
+    if dependency.kind == ServiceKind::Service {
+        return Ok(());
+    }
+
+    let deadline = Instant::now() + TASK_TIMEOUT;
+    let mut announced = false;
+    loop {
+        match pty_manager::run_state(&dependency.id) {
+            RunState::Succeeded => return Ok(()),
+            RunState::Failed => {
+                return Err(format!("not starting, task '{}' failed", dependency.name))
+            }
+            _ if Instant::now() > deadline => {
+                pty_manager::emit_banner(
+                    &service.id,
+                    &format!(
+                        "task '{}' didn't complete within {}s, not starting",
+                        dependency.name,
+                        TASK_TIMEOUT.as_secs()
+                    ),
+                );
+                return Err(format!("not starting, task '{}' timed out", dependency.name));
+            }
+            // Still running, paused, or not spawned yet
+            RunState::Running | RunState::Paused | RunState::Pending => {
+                if !announced {
+                    pty_manager::emit_banner(
+                        &service.id,
+                        &format!("waiting for task '{}' to complete", dependency.name),
+                    );
+                    announced = true;
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+}
+
+/// Starts every service that isn't running yet, tasks that already succeeded
+/// aside. Each service waits for its own dependencies, so independent
+/// services don't hold each other up.
+pub fn start_all() {
+    start_matching(|_| true);
+}
+
+/// Starts every service of a group, along with whatever they depend on.
+pub fn start_group(group: &str) {
+    start_matching(|service| service.groups.iter().any(|g| g == group));
+}
+
+fn start_matching(predicate: impl Fn(&ServiceConfig) -> bool) {
+    let Ok(config) = crate::config::get_config() else {
+        return;
+    };
+
+    let services = config.dependency_order().into_iter().flat_map(replicas::instances);
+    for service in services {
+        if !predicate(&service) {
+            continue;
+        }
+
+        // Claimed right away, so dependents started alongside don't start it too
+        let claimed = pty_manager::claim_start(&service.id, |state| match state {
+            RunState::Running | RunState::Paused => false,
+            RunState::Succeeded => service.kind != ServiceKind::Task,
+            RunState::Pending | RunState::Failed => true,
+        });
+        if !claimed {
+            continue;
+        }
+
+        std::thread::spawn(move || {
+            if let Err(e) = start_claimed(&service) {
+                emitter::emit(service.id, serde_json::json!({ "type": "error", "data": e }));
+            }
+        });
+    }
+}
+
+/// Stops every running service, dependents before their dependencies.
+/// Blocks until all of them are stopped.
+pub fn stop_all() -> Vec<ServiceConfig> {
+    let Ok(config) = crate::config::get_config() else {
+        return vec![];
+    };
+
+    let mut stopped = vec![];
+    let services = config.dependency_order().into_iter().flat_map(replicas::instances);
+    for service in services.rev() {
+        if !pty_manager::is_running(&service.id) {
+            continue;
+        }
+
+        if let Err(e) = pty_manager::stop_pty(&service.id) {
+            emitter::emit(
+                service.id.clone(),
+                serde_json::json!({ "type": "error", "data": e }),
+            );
+        }
+        stopped.push(service);
+    }
+
+    stopped
+}
+
+/// Stops every running service and starts them again in dependency order.
+pub fn restart_all() {
+    let stopped = stop_all();
+
+    // The reader threads tear the sessions down once the processes are reaped
+    let deadline = Instant::now() + Duration::from_secs(10);
+    for service in &stopped {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        pty_manager::wait_stopped(&service.id, timeout);
+    }
+
+    for service in stopped.into_iter().rev() {
+        std::thread::spawn(move || {
+            if let Err(e) = start_service(&service) {
+                emitter::emit(service.id, serde_json::json!({ "type": "error", "data": e }));
+            }
+        });
+    }
+}
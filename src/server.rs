@@ -0,0 +1,305 @@
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tiny_http::{Header, Method, ReadWrite, Request, Response, Server};
+use wry::http;
+
+use crate::{assets, remote};
+
+// Browsers' WebSockets open at once, each one holding a thread. A browser
+// opens two, one for events and one for commands.
+const MAX_SESSIONS: usize = 64;
+
+/// What holding a token allows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Sees everything and controls the services
+    Operator,
+    /// Sees the services and their output, but can't act on them
+    Viewer,
+}
+
+impl Role {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Role::Operator => "operator",
+            Role::Viewer => "viewer",
+        }
+    }
+}
+
+/// A running server, with the URL to open for each role, token included.
+pub struct Served {
+    pub urls: Vec<(Role, String)>,
+}
+
+impl Served {
+    pub fn url(&self, role: Role) -> Option<&str> {
+        self.urls
+            .iter()
+            .find(|(r, _)| *r == role)
+            .map(|(_, url)| url.as_str())
+    }
+}
+
+struct Shared {
+    tokens: Vec<(Role, String)>,
+    cookie: String,
+    // Whether browsers can connect to the services through /ws
+    websocket: bool,
+    // WebSocket sessions currently open
+    sessions: Arc<AtomicUsize>,
+}
+
+impl Shared {
+    fn role_of(&self, token: &str) -> Option<Role> {
+        self.tokens
+            .iter()
+            .find(|(_, t)| same_token(t, token))
+            .map(|(role, _)| *role)
+    }
+}
+
+// A random hex string, new on every launch
+fn new_token() -> String {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).expect("Failed to generate a token");
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+// Compares without returning early, so timing doesn't leak the token
+fn same_token(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn header_value<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+fn query_token(url: &str) -> Option<&str> {
+    let (_, query) = url.split_once('?')?;
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("token="))
+}
+
+fn cookie_value<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .filter(|header| header.field.equiv("Cookie"))
+        .flat_map(|header| header.value.as_str().split(';'))
+        .find_map(|cookie| {
+            let (key, value) = cookie.trim().split_once('=')?;
+            (key == name).then_some(value)
+        })
+}
+
+fn plain(status: u16, text: &str) -> Response<Cursor<Vec<u8>>> {
+    Response::from_string(text).with_status_code(status)
+}
+
+// The request as the asset lookup, shared with the custom protocol, takes it
+fn to_http(request: &Request) -> http::Request<Vec<u8>> {
+    let mut builder = http::Request::builder()
+        .method(request.method().as_str())
+        .uri(request.url());
+    for header in request.headers() {
+        builder = builder.header(header.field.as_str().as_str(), header.value.as_str());
+    }
+    builder.body(Vec::new()).unwrap_or_default()
+}
+
+fn from_http(response: http::Response<Cow<'static, [u8]>>) -> Response<Cursor<Vec<u8>>> {
+    let mut converted =
+        Response::from_data(response.body().to_vec()).with_status_code(response.status().as_u16());
+    for (name, value) in response.headers() {
+        if let Ok(value) = value.to_str() {
+            converted.add_header(header(name.as_str(), value));
+        }
+    }
+    converted
+}
+
+// Whether the page asking for a WebSocket was served from this very host and
+// port. Browsers let any page open WebSockets anywhere, cookies included, so
+// without this another site, or another local port, could drive the
+// services. Clients that aren't browsers send no Origin.
+fn same_origin(request: &Request) -> bool {
+    let Some(origin) = header_value(request, "Origin") else {
+        return true;
+    };
+    let origin = origin
+        .split_once("://")
+        .map_or(origin, |(_, authority)| authority);
+    header_value(request, "Host").is_some_and(|host| host.eq_ignore_ascii_case(origin))
+}
+
+// The TCP connection a request came on, which tiny_http keeps to itself,
+// found among the open files by the address of its peer. Sessions use it to
+// time out reads and writes.
+#[cfg(unix)]
+fn connection(request: &Request) -> Option<TcpStream> {
+    use std::os::fd::{BorrowedFd, RawFd};
+
+    let peer = *request.remote_addr()?;
+    std::fs::read_dir("/dev/fd")
+        .ok()?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<RawFd>().ok())
+        .find_map(|fd| {
+            // Duplicating a file that closed meanwhile fails, and whatever
+            // took its number isn't connected to the peer
+            let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+            let stream = TcpStream::from(fd.try_clone_to_owned().ok()?);
+            (stream.peer_addr().ok()? == peer).then_some(stream)
+        })
+}
+
+#[cfg(not(unix))]
+fn connection(_request: &Request) -> Option<TcpStream> {
+    None
+}
+
+// Completes the WebSocket handshake and hands the connection to `session`,
+// on a thread of its own
+fn accept_websocket(
+    request: Request,
+    role: Role,
+    sessions: &Arc<AtomicUsize>,
+    session: fn(Box<dyn ReadWrite + Send>, Option<TcpStream>, Role),
+) {
+    if !same_origin(&request) {
+        let _ = request.respond(plain(403, "Forbidden"));
+        return;
+    }
+
+    let upgrade = header_value(&request, "Upgrade")
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    let Some(key) = header_value(&request, "Sec-WebSocket-Key").filter(|_| upgrade) else {
+        let _ = request.respond(plain(400, "Expected a WebSocket"));
+        return;
+    };
+
+    if sessions.fetch_add(1, Ordering::SeqCst) >= MAX_SESSIONS {
+        sessions.fetch_sub(1, Ordering::SeqCst);
+        let _ = request.respond(plain(503, "Too many connections"));
+        return;
+    }
+
+    let connection = connection(&request);
+    let accept = tungstenite::handshake::derive_accept_key(key.as_bytes());
+    let response = Response::empty(101).with_header(header("Sec-WebSocket-Accept", &accept));
+    let stream = request.upgrade("websocket", response);
+
+    let sessions = sessions.clone();
+    std::thread::spawn(move || {
+        session(stream, connection, role);
+        sessions.fetch_sub(1, Ordering::SeqCst);
+    });
+}
+
+fn handle(request: Request, shared: &Shared) {
+    if !matches!(request.method(), Method::Get | Method::Head) {
+        let _ = request.respond(plain(405, "Method Not Allowed"));
+        return;
+    }
+
+    // The token comes in the URL once, then lives in a cookie so the page's
+    // own requests carry it. Redirecting drops it from the address bar.
+    if let Some(token) = query_token(request.url()).filter(|token| shared.role_of(token).is_some())
+    {
+        let path = request.url().split('?').next().unwrap_or("/");
+        let response = Response::empty(303)
+            .with_header(header("Location", path))
+            .with_header(header(
+                "Set-Cookie",
+                &format!(
+                    "{}={}; Path=/; HttpOnly; SameSite=Strict",
+                    shared.cookie, token
+                ),
+            ));
+        let _ = request.respond(response);
+        return;
+    }
+
+    let Some(role) = cookie_value(&request, &shared.cookie).and_then(|token| shared.role_of(token))
+    else {
+        let _ = request.respond(plain(401, "Unauthorized"));
+        return;
+    };
+
+    let path = request.url().split('?').next().unwrap_or_default();
+    match path {
+        "/ws" if shared.websocket => {
+            accept_websocket(request, role, &shared.sessions, remote::events);
+            return;
+        }
+        "/ws/commands" if shared.websocket => {
+            accept_websocket(request, role, &shared.sessions, remote::commands);
+            return;
+        }
+        _ => {}
+    }
+
+    let response = from_http(assets::response(&to_http(&request), true));
+    let _ = request.respond(response);
+}
+
+/// Serves the frontend on `addr`, with a token for each role, answering only
+/// requests that carry one of them. With `websocket`, browsers can also reach
+/// the services through /ws.
+pub fn start(addr: SocketAddr, roles: &[Role], websocket: bool) -> Result<Served, String> {
+    let server = Server::http(addr).map_err(|e| format!("failed to listen on {addr}: {e}"))?;
+    let addr = server
+        .server_addr()
+        .to_ip()
+        .ok_or_else(|| "not listening on an IP address".to_string())?;
+
+    if !addr.ip().is_loopback() {
+        eprintln!("Serving the UI on {addr}, which other machines may reach");
+    }
+
+    // A wildcard address can't be connected to, use the loopback instead
+    let host = match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    let base = format!("http://{}", SocketAddr::new(host, addr.port()));
+
+    let tokens: Vec<(Role, String)> = roles.iter().map(|role| (*role, new_token())).collect();
+    let urls = tokens
+        .iter()
+        .map(|(role, token)| (*role, format!("{base}/?token={token}")))
+        .collect();
+
+    let shared = Shared {
+        tokens,
+        // Cookies don't tell ports apart, each instance needs its own name
+        cookie: format!("runz_token_{}", addr.port()),
+        websocket,
+        sessions: Arc::new(AtomicUsize::new(0)),
+    };
+
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            handle(request, &shared);
+        }
+    });
+
+    Ok(Served { urls })
+}
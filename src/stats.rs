@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::{emitter, pty_manager};
+
+// How often resource usage is sampled and reported
+const INTERVAL: Duration = Duration::from_secs(2);
+
+/// Resource usage of a process and everything it spawned.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    // CPU time used so far, in seconds
+    pub cpu_time: f64,
+    // Resident memory, in bytes
+    pub memory: u64,
+}
+
+#[cfg(target_os = "linux")]
+struct ProcStat {
+    pid: u32,
+    ppid: u32,
+    session: u32,
+    ticks: u64,
+    rss_pages: u64,
+}
+
+// Parses /proc/<pid>/stat. The command name is wrapped in parentheses and
+// may contain spaces, so fields are counted from the last `)`.
+#[cfg(target_os = "linux")]
+fn read_stat(pid: u32) -> Option<ProcStat> {
+    let data = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let fields: Vec<&str> = data.rsplit_once(')')?.1.split_whitespace().collect();
+    let field = |index: usize| fields.get(index)?.parse::<u64>().ok();
+
+    Some(ProcStat {
+        pid,
+        ppid: field(1)? as u32,
+        session: field(3)? as u32,
+        ticks: field(11)? + field(12)?,
+        rss_pages: field(21)?,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn all_processes() -> Vec<ProcStat> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(read_stat)
+        .collect()
+}
+
+// `root` and its descendants, including the ones that were reparented but
+// still belong to its session. Empty if `root` is gone.
+#[cfg(target_os = "linux")]
+fn tree_members(root: u32, processes: &[ProcStat]) -> Vec<u32> {
+    if !processes.iter().any(|p| p.pid == root) {
+        return vec![];
+    }
+
+    let mut members = vec![root];
+    loop {
+        let before = members.len();
+        for process in processes {
+            let belongs = process.session == root || members.contains(&process.ppid);
+            if belongs && !members.contains(&process.pid) {
+                members.push(process.pid);
+            }
+        }
+        if members.len() == before {
+            return members;
+        }
+    }
+}
+
+/// The processes running at one point in time. Looking several services up
+/// in the same snapshot reads /proc once rather than once per service.
+pub struct Snapshot {
+    #[cfg(target_os = "linux")]
+    processes: Vec<ProcStat>,
+}
+
+impl Snapshot {
+    #[cfg(target_os = "linux")]
+    pub fn take() -> Self {
+        Self {
+            processes: all_processes(),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn take() -> Self {
+        Self {}
+    }
+
+    /// The pids of `root` and everything it spawned.
+    #[cfg(target_os = "linux")]
+    pub fn tree(&self, root: u32) -> Vec<u32> {
+        tree_members(root, &self.processes)
+    }
+
+    /// Sums the usage of `root` and everything it spawned.
+    #[cfg(target_os = "linux")]
+    pub fn usage(&self, root: u32) -> Option<Usage> {
+        let members = self.tree(root);
+        if members.is_empty() {
+            return None;
+        }
+
+        let ticks_per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64;
+
+        let mut usage = Usage::default();
+        for process in self.processes.iter().filter(|p| members.contains(&p.pid)) {
+            usage.cpu_time += process.ticks as f64 / ticks_per_second;
+            usage.memory += process.rss_pages * page_size;
+        }
+        Some(usage)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn usage(&self, _root: u32) -> Option<Usage> {
+        None
+    }
+}
+
+/// The pids of `root` and everything it spawned.
+#[cfg(target_os = "linux")]
+pub fn process_tree(root: u32) -> Vec<u32> {
+    Snapshot::take().tree(root)
+}
+
+/// Reports the pid, uptime, restart count and, on Linux, the CPU and memory
+/// usage of every running service as `stats` events.
+pub fn start() {
+    std::thread::spawn(|| {
+        // Previous CPU time of each service's process, to compute CPU%
+        let mut previous: HashMap<String, (u32, f64, Instant)> = HashMap::new();
+
+        loop {
+            std::thread::sleep(INTERVAL);
+
+            let sessions = pty_manager::sessions();
+            previous.retain(|id, _| sessions.iter().any(|s| &s.id == id));
+
+            let snapshot = Snapshot::take();
+            for session in sessions {
+                let usage = session.pid.and_then(|pid| snapshot.usage(pid));
+                let now = Instant::now();
+
+                let cpu = match (session.pid, usage) {
+                    (Some(pid), Some(usage)) => {
+                        let cpu = match previous.get(&session.id) {
+                            Some((last_pid, last_time, last_at)) if *last_pid == pid => {
+                                let elapsed = now.duration_since(*last_at).as_secs_f64();
+                                Some((usage.cpu_time - last_time).max(0.0) / elapsed * 100.0)
+                            }
+                            _ => None,
+                        };
+                        previous.insert(session.id.clone(), (pid, usage.cpu_time, now));
+                        cpu
+                    }
+                    _ => None,
+                };
+
+                emitter::emit(
+                    session.id,
+                    serde_json::json!({
+                        "type": "stats",
+                        "data": {
+                            "pid": session.pid,
+                            "uptime": session.started_at.elapsed().as_secs(),
+                            "restarts": session.restarts,
+                            "cpu": cpu,
+                            "memory": usage.map(|usage| usage.memory),
+                        },
+                    }),
+                );
+            }
+        }
+    });
+}
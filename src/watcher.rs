@@ -0,0 +1,205 @@
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use parking_lot::Mutex;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use crate::{
+    config::{ServiceConfig, WatchAction},
+    pty_manager::{self, RunState},
+};
+
+// A service's watcher, with the directories it was told about
+struct ServiceWatcher {
+    watcher: RecommendedWatcher,
+    dirs: HashSet<PathBuf>,
+    // Directories whose whole content is excluded, never watched
+    skip: GlobSet,
+}
+
+impl ServiceWatcher {
+    // Watches `dir` and the directories below it, one by one so excluded ones
+    // like node_modules or target don't cost a watch each
+    fn watch_tree(&mut self, root: &Path, dir: &Path) -> Result<(), String> {
+        let relative = dir.strip_prefix(root).unwrap_or(dir);
+        if (!relative.as_os_str().is_empty() && self.skip.is_match(relative))
+            || !self.dirs.insert(dir.to_path_buf())
+        {
+            return Ok(());
+        }
+
+        self.watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(|e| format!("failed to watch {}: {e}", dir.display()))?;
+
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Ok(());
+        };
+        for entry in entries.flatten() {
+            // Symlinks aren't followed, they could loop
+            if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+                self.watch_tree(root, &entry.path())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Keeps the watchers alive, one per service
+lazy_static::lazy_static! {
+    static ref WATCHERS: Mutex<HashMap<String, ServiceWatcher>> = Mutex::new(HashMap::new());
+}
+
+fn build_globset(patterns: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| e.to_string())?);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+// The directories `dir/**` exclude patterns leave nothing to watch in
+fn excluded_dirs(patterns: &[String]) -> Result<GlobSet, String> {
+    let dirs: Vec<String> = patterns
+        .iter()
+        .filter_map(|pattern| pattern.strip_suffix("/**"))
+        .map(str::to_string)
+        .collect();
+    build_globset(&dirs)
+}
+
+/// Starts watching the service's working directory, if it has a `watch` block
+/// and is not being watched already.
+pub fn watch_service(service: &ServiceConfig) -> Result<(), String> {
+    let Some(watch) = &service.watch else {
+        return Ok(());
+    };
+
+    let mut watchers = WATCHERS.lock();
+    if watchers.contains_key(&service.id) {
+        return Ok(());
+    }
+
+    let include = build_globset(&watch.include)?;
+    let exclude = build_globset(&watch.exclude)?;
+    let skip = excluded_dirs(&watch.exclude)?;
+
+    // Event paths may come canonicalized (e.g. /private/var on macOS)
+    let mut roots = vec![PathBuf::from(&service.wdir)];
+    if let Ok(canonical) = std::fs::canonicalize(&service.wdir) {
+        roots.push(canonical);
+    }
+
+    // Opening or reading a file doesn't change it
+    let (changes, changed) = mpsc::channel::<PathBuf>();
+    let watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+        let Ok(event) = result else {
+            return;
+        };
+        if !matches!(event.kind, EventKind::Access(_)) {
+            for path in event.paths {
+                let _ = changes.send(path);
+            }
+        }
+    })
+    .map_err(|e| format!("failed to create watcher: {e}"))?;
+
+    // Waits for changes to settle, then handles them at once
+    let service_clone = service.clone();
+    let debounce = Duration::from_millis(watch.debounce);
+    std::thread::spawn(move || {
+        while let Ok(first) = changed.recv() {
+            let mut paths = vec![first];
+            while let Ok(path) = changed.recv_timeout(debounce) {
+                paths.push(path);
+            }
+
+            // Directories created since need watches of their own, removed
+            // ones may come back
+            let wdir = Path::new(&service_clone.wdir);
+            if let Some(watcher) = WATCHERS.lock().get_mut(&service_clone.id) {
+                for path in &paths {
+                    if path.is_dir() {
+                        let _ = watcher.watch_tree(wdir, path);
+                    } else if !path.exists() {
+                        watcher.dirs.retain(|dir| !dir.starts_with(path));
+                    }
+                }
+            }
+
+            let changed = paths.iter().find_map(|path| {
+                let relative = roots.iter().find_map(|root| path.strip_prefix(root).ok())?;
+                // The directory itself isn't one of its files
+                (!relative.as_os_str().is_empty()
+                    && include.is_match(relative)
+                    && !exclude.is_match(relative))
+                .then(|| relative.to_path_buf())
+            });
+
+            if let Some(path) = changed {
+                on_change(&service_clone, &path);
+            }
+        }
+    });
+
+    let mut watcher = ServiceWatcher {
+        watcher,
+        dirs: HashSet::new(),
+        skip,
+    };
+    let wdir = Path::new(&service.wdir);
+    watcher.watch_tree(wdir, wdir)?;
+
+    watchers.insert(service.id.clone(), watcher);
+
+    Ok(())
+}
+
+fn on_change(service: &ServiceConfig, path: &Path) {
+    // A service stopped by the user stays stopped
+    if !pty_manager::is_running(&service.id) {
+        return;
+    }
+
+    let Some(watch) = &service.watch else {
+        return;
+    };
+
+    match watch.action {
+        // Restarting would unfreeze a service that was paused on purpose
+        WatchAction::Restart if pty_manager::run_state(&service.id) == RunState::Paused => {
+            pty_manager::emit_banner(
+                &service.id,
+                &format!("{} changed, not restarting while paused", path.display()),
+            );
+        }
+        WatchAction::Restart => {
+            pty_manager::emit_banner(
+                &service.id,
+                &format!("{} changed, restarting", path.display()),
+            );
+            if let Err(e) = pty_manager::restart_pty(service) {
+                pty_manager::emit_banner(&service.id, &format!("restart failed: {e}"));
+            }
+        }
+        WatchAction::Signal => {
+            let name = watch.signal.as_deref().unwrap_or_default();
+            pty_manager::emit_banner(
+                &service.id,
+                &format!("{} changed, sending {}", path.display(), name),
+            );
+            let result = match pty_manager::parse_signal(name) {
+                Some(signal) => pty_manager::signal_pty(&service.id, signal),
+                // The config only lets unknown names through where signals don't exist
+                None => Err("signals are not supported on this platform".to_string()),
+            };
+            if let Err(e) = result {
+                pty_manager::emit_banner(&service.id, &format!("signal failed: {e}"));
+            }
+        }
+    }
+}
@@ -0,0 +1,62 @@
+#![cfg(unix)]
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixListener;
+use std::process::Command;
+
+// `runz ctl status --json` is meant to be piped into tools like jq, nothing
+// but the JSON may reach stdout, config loading messages included
+#[test]
+fn status_json_prints_only_json() {
+    let name = format!("ctl-json-{}", std::process::id());
+    let workspace = std::env::temp_dir().join(&name);
+    std::fs::create_dir_all(workspace.join(".git")).unwrap();
+    std::fs::write(
+        workspace.join("runz.yml"),
+        format!("name: {name}\nservices:\n  - name: api\n    prog: sleep\n    args: ['60']\n"),
+    )
+    .unwrap();
+    std::fs::write(
+        workspace.join("runz.local.yml"),
+        "services:\n  - name: api\n    env: {DEBUG: '1'}\n",
+    )
+    .unwrap();
+
+    // Stands in for a running runz, answering the status request
+    let tempdir = std::env::temp_dir().join("runz").join(&name);
+    std::fs::create_dir_all(&tempdir).unwrap();
+    let socket = tempdir.join("control.sock");
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket).unwrap();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+            line.clear();
+        }
+        let body = r#"[{"id":"0","name":"api","state":"running","ready":true}]"#;
+        write!(stream, "HTTP/1.0 200 OK\r\n\r\n{body}").unwrap();
+    });
+
+    let output = Command::new(env!("CARGO_BIN_EXE_runz"))
+        .args(["ctl", "status", "--json"])
+        .current_dir(&workspace)
+        .env_remove("RUNZ_CONFIG")
+        .output()
+        .unwrap();
+    server.join().unwrap();
+    let _ = std::fs::remove_dir_all(&workspace);
+    let _ = std::fs::remove_dir_all(&tempdir);
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let services: serde_json::Value = serde_json::from_str(&stdout)
+        .unwrap_or_else(|err| panic!("stdout isn't JSON ({err}): {stdout}"));
+    assert_eq!(services[0]["name"], "api");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Using local override"));
+}